@@ -1,4 +1,43 @@
-use super::{Value, EvalError};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{Callable, Environment, Value, EvalError, Evaluator};
+use super::evaluator::is_truthy;
+use super::value::format_parameters;
+use crate::parser::Parser;
+use crate::pretty_print::PrettyPrinter;
+use crate::tokenizer::Tokenizer;
+
+thread_local! {
+    // State of the linear congruential generator behind `random`
+    static RANDOM_STATE: Cell<u64> = Cell::new(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0),
+    );
+
+    // Buffers for with-output-to-string; output goes to the innermost one, else stdout
+    static OUTPUT_CAPTURE: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+
+    // Handlers installed by with-exception-handler, innermost last
+    static EXCEPTION_HANDLERS: RefCell<Vec<Value>> = const { RefCell::new(Vec::new()) };
+}
+
+fn emit_output(text: &str) {
+    OUTPUT_CAPTURE.with(|capture| match capture.borrow_mut().last_mut() {
+        Some(buffer) => buffer.push_str(text),
+        None => {
+            print!("{}", text);
+        }
+    });
+}
 
 
 fn check_num_args(func_name: &str, args: &[Value], expected: usize) -> Result<(), EvalError> {
@@ -35,6 +74,32 @@ fn get_num_arg(func_name: &str, arg: &Value) -> Result<f64, EvalError> {
     }
 }
 
+fn get_int_arg(func_name: &str, arg: &Value) -> Result<i64, EvalError> {
+    let n = get_num_arg(func_name, arg)?;
+    if n.fract() != 0.0 || !n.is_finite() {
+        return Err(EvalError::TypeError(format!("{} expects integers", func_name)));
+    }
+    // `as` would saturate anything outside i64 instead of failing
+    if n < i64::MIN as f64 || n >= i64::MAX as f64 {
+        return Err(EvalError::TypeError(format!("{} expects integers that fit in 64 bits", func_name)));
+    }
+    Ok(n as i64)
+}
+
+// Reads the length of a sequence to allocate; absurd lengths are refused rather than aborting the process
+fn get_count_arg(func_name: &str, arg: &Value, max: usize) -> Result<usize, EvalError> {
+    let count = get_int_arg(func_name, arg)?;
+    match usize::try_from(count) {
+        Ok(count) if count <= max => Ok(count),
+        Ok(_) => Err(EvalError::TypeError(format!("{} expects a count of at most {}", func_name, max))),
+        Err(_) => Err(EvalError::TypeError(format!("{} expects a non-negative count", func_name))),
+    }
+}
+
+fn get_all_int_args(func_name: &str, args: &[Value]) -> Result<Vec<i64>, EvalError> {
+    args.iter().map(|arg| get_int_arg(func_name, arg)).collect()
+}
+
 fn get_two_num_args(func_name: &str, args: &[Value]) -> Result<(f64, f64), EvalError> {
     check_num_args(func_name, args, 2)?;
     let a = get_num_arg(func_name, &args[0])?;
@@ -48,6 +113,27 @@ fn get_all_num_args(func_name: &str, args: Vec<Value>) -> Result<Vec<f64>, EvalE
         .collect()
 }
 
+fn get_string_arg<'a>(func_name: &str, arg: &'a Value) -> Result<&'a str, EvalError> {
+    if let Value::String(s) = arg {
+        Ok(s)
+    } else {
+        Err(EvalError::TypeError(format!("{} expects strings", func_name)))
+    }
+}
+
+fn get_two_string_args<'a>(func_name: &str, args: &'a [Value]) -> Result<(&'a str, &'a str), EvalError> {
+    check_num_args(func_name, args, 2)?;
+    let a = get_string_arg(func_name, &args[0])?;
+    let b = get_string_arg(func_name, &args[1])?;
+    Ok((a, b))
+}
+
+fn get_list_arg(func_name: &str, arg: &Value) -> Result<Vec<Value>, EvalError> {
+    arg.clone()
+        .into_vec()
+        .map_err(|_| EvalError::TypeError(format!("{} expects a proper list", func_name)))
+}
+
 // Arithmetic functions
 pub fn builtin_add(args: Vec<Value>) -> Result<Value, EvalError> {
     let numbers = get_all_num_args("+", args)?;
@@ -79,6 +165,73 @@ pub fn builtin_div(args: Vec<Value>) -> Result<Value, EvalError> {
     Ok(Value::Number(numerator / denominator))
 }
 
+pub fn builtin_max(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_min_args("max", &args, 1)?;
+    let nums = get_all_num_args("max", args)?;
+    Ok(Value::Number(nums.into_iter().fold(f64::NEG_INFINITY, f64::max)))
+}
+
+pub fn builtin_min(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_min_args("min", &args, 1)?;
+    let nums = get_all_num_args("min", args)?;
+    Ok(Value::Number(nums.into_iter().fold(f64::INFINITY, f64::min)))
+}
+
+// Numeric predicates
+pub fn builtin_is_number(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("number?", &args, 1)?;
+    Ok(Value::Boolean(matches!(args[0], Value::Number(_))))
+}
+
+pub fn builtin_is_finite(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("finite?", &args, 1)?;
+    Ok(Value::Boolean(get_num_arg("finite?", &args[0])?.is_finite()))
+}
+
+pub fn builtin_is_infinite(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("infinite?", &args, 1)?;
+    Ok(Value::Boolean(get_num_arg("infinite?", &args[0])?.is_infinite()))
+}
+
+pub fn builtin_is_nan(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("nan?", &args, 1)?;
+    Ok(Value::Boolean(get_num_arg("nan?", &args[0])?.is_nan()))
+}
+
+pub fn builtin_is_integer(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("integer?", &args, 1)?;
+    let n = get_num_arg("integer?", &args[0])?;
+    Ok(Value::Boolean(n.is_finite() && n.fract() == 0.0))
+}
+
+// With every number stored as an f64 there is no separate exactness, so this matches integer?
+pub fn builtin_is_exact_integer(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("exact-integer?", &args, 1)?;
+    Ok(Value::Boolean(matches!(args[0], Value::Number(n) if n.is_finite() && n.fract() == 0.0)))
+}
+
+pub fn builtin_is_string_number(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("string-number?", &args, 1)?;
+    let s = get_string_arg("string-number?", &args[0])?;
+    Ok(Value::Boolean(s.parse::<f64>().is_ok_and(|n| n.is_finite())))
+}
+
+// Every number is an f64 for now, so exactness conversions only validate their argument
+pub fn builtin_exact_to_inexact(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("exact->inexact", &args, 1)?;
+    let n = get_num_arg("exact->inexact", &args[0])?;
+    Ok(Value::Number(n))
+}
+
+pub fn builtin_inexact_to_exact(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("inexact->exact", &args, 1)?;
+    let n = get_num_arg("inexact->exact", &args[0])?;
+    if !n.is_finite() {
+        return Err(EvalError::TypeError("inexact->exact expects a finite number".to_string()));
+    }
+    Ok(Value::Number(n))
+}
+
 // Comparison functions
 pub fn builtin_eq(args: Vec<Value>) -> Result<Value, EvalError> {
     check_num_args("=", &args, 2)?;
@@ -110,14 +263,1954 @@ pub fn builtin_le(args: Vec<Value>) -> Result<Value, EvalError> {
     Ok(Value::Boolean(a <= b))
 }
 
+pub fn builtin_boolean_eq(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_min_args("boolean=?", &args, 2)?;
+    let bools = args
+        .iter()
+        .map(|arg| match arg {
+            Value::Boolean(b) => Ok(*b),
+            _ => Err(EvalError::TypeError("boolean=? expects booleans".to_string())),
+        })
+        .collect::<Result<Vec<bool>, EvalError>>()?;
+    Ok(Value::Boolean(bools.windows(2).all(|pair| pair[0] == pair[1])))
+}
+
+fn get_two_symbol_args<'a>(func_name: &str, args: &'a [Value]) -> Result<(&'a str, &'a str), EvalError> {
+    check_num_args(func_name, args, 2)?;
+    match (&args[0], &args[1]) {
+        (Value::Symbol(a), Value::Symbol(b)) => Ok((a, b)),
+        _ => Err(EvalError::TypeError(format!("{} expects symbols", func_name))),
+    }
+}
+
+pub fn builtin_symbol_eq(args: Vec<Value>) -> Result<Value, EvalError> {
+    let (a, b) = get_two_symbol_args("symbol=?", &args)?;
+    Ok(Value::Boolean(a == b))
+}
+
+pub fn builtin_symbol_lt(args: Vec<Value>) -> Result<Value, EvalError> {
+    let (a, b) = get_two_symbol_args("symbol<?", &args)?;
+    Ok(Value::Boolean(a < b))
+}
+
+pub fn builtin_symbol_gt(args: Vec<Value>) -> Result<Value, EvalError> {
+    let (a, b) = get_two_symbol_args("symbol>?", &args)?;
+    Ok(Value::Boolean(a > b))
+}
+
+pub fn builtin_symbol_le(args: Vec<Value>) -> Result<Value, EvalError> {
+    let (a, b) = get_two_symbol_args("symbol<=?", &args)?;
+    Ok(Value::Boolean(a <= b))
+}
+
+pub fn builtin_symbol_ge(args: Vec<Value>) -> Result<Value, EvalError> {
+    let (a, b) = get_two_symbol_args("symbol>=?", &args)?;
+    Ok(Value::Boolean(a >= b))
+}
+
 // Other built-ins
+fn join_displayed(args: &[Value]) -> String {
+    let parts: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    parts.join(" ")
+}
+
 pub fn builtin_print(args: Vec<Value>) -> Result<Value, EvalError> {
-    for (i, arg) in args.iter().enumerate() {
-        print!("{}", arg);
-        if i < args.len() - 1 {
-            print!(" ");
+    emit_output(&join_displayed(&args));
+    emit_output("\n");
+    Ok(Value::Void)
+}
+
+pub fn builtin_print_star(args: Vec<Value>) -> Result<Value, EvalError> {
+    emit_output(&join_displayed(&args));
+    Ok(Value::Void)
+}
+
+// Diagnostics go straight to stderr and are never captured by with-output-to-string
+pub fn builtin_eprint(args: Vec<Value>) -> Result<Value, EvalError> {
+    eprint!("{}", join_displayed(&args));
+    Ok(Value::Void)
+}
+
+pub fn builtin_eprintln(args: Vec<Value>) -> Result<Value, EvalError> {
+    eprintln!("{}", join_displayed(&args));
+    Ok(Value::Void)
+}
+
+pub fn builtin_display(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("display", &args, 1)?;
+    emit_output(&args[0].to_string());
+    Ok(Value::Void)
+}
+
+pub fn builtin_pp(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("pp", &args, 1)?;
+    emit_output(&PrettyPrinter::new(80).format(&args[0]));
+    emit_output("\n");
+    Ok(Value::Void)
+}
+
+pub fn builtin_write(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("write", &args, 1)?;
+    emit_output(&args[0].write_form());
+    Ok(Value::Void)
+}
+
+pub fn builtin_format(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_min_args("format", &args, 1)?;
+    let mut args = args.into_iter();
+
+    let (to_stdout, template) = match args.next() {
+        Some(Value::String(template)) => (false, template),
+        Some(Value::Boolean(destination)) => match args.next() {
+            Some(Value::String(template)) => (destination, template),
+            _ => {
+                return Err(EvalError::TypeError(
+                    "format expects a format string after the destination".to_string(),
+                ));
+            }
+        },
+        _ => {
+            return Err(EvalError::TypeError(
+                "format expects a format string or a boolean destination".to_string(),
+            ));
+        }
+    };
+
+    let mut output = String::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '~' {
+            output.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('%') => output.push('\n'),
+            Some('~') => output.push('~'),
+            Some(directive @ ('a' | 's' | 'd' | 'x')) => {
+                let arg = args.next().ok_or_else(|| {
+                    EvalError::WrongNumArgs(format!("format: missing argument for ~{}", directive))
+                })?;
+                match directive {
+                    'a' => output.push_str(&arg.to_string()),
+                    's' => output.push_str(&arg.write_form()),
+                    'd' => {
+                        let n = get_num_arg("format ~d", &arg)?;
+                        if !n.is_finite() || n.fract() != 0.0 {
+                            return Err(EvalError::TypeError("format ~d expects integers".to_string()));
+                        }
+                        output.push_str(&n.to_string());
+                    }
+                    _ => {
+                        let n = get_int_arg("format ~x", &arg)?;
+                        if n < 0 {
+                            output.push_str(&format!("-{:x}", n.unsigned_abs()));
+                        } else {
+                            output.push_str(&format!("{:x}", n));
+                        }
+                    }
+                }
+            }
+            Some(other) => {
+                return Err(EvalError::TypeError(format!("format: unknown directive ~{}", other)));
+            }
+            None => {
+                return Err(EvalError::TypeError(
+                    "format: incomplete directive at end of string".to_string(),
+                ));
+            }
+        }
+    }
+
+    let extra = args.count();
+    if extra > 0 {
+        return Err(EvalError::WrongNumArgs(format!(
+            "format: {} argument(s) left over after formatting",
+            extra
+        )));
+    }
+
+    if to_stdout {
+        emit_output(&output);
+        Ok(Value::Void)
+    } else {
+        Ok(Value::String(output))
+    }
+}
+
+fn next_random() -> f64 {
+    RANDOM_STATE.with(|state| {
+        let next = state
+            .get()
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        state.set(next);
+        // The high 53 bits give a uniform float in [0, 1)
+        (next >> 11) as f64 / (1u64 << 53) as f64
+    })
+}
+
+// format! panics on a precision or width that does not fit in a u16
+const MAX_FORMAT_ARG: usize = u16::MAX as usize;
+
+fn get_precision_arg(func_name: &str, arg: &Value) -> Result<usize, EvalError> {
+    let precision = get_int_arg(func_name, arg)?;
+    match usize::try_from(precision) {
+        Ok(precision) if precision <= MAX_FORMAT_ARG => Ok(precision),
+        Ok(_) => Err(EvalError::TypeError(format!(
+            "{} expects a precision of at most {}",
+            func_name, MAX_FORMAT_ARG
+        ))),
+        Err(_) => Err(EvalError::TypeError(format!("{} expects a non-negative precision", func_name))),
+    }
+}
+
+pub fn builtin_number_format(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("number-format", &args, 2)?;
+    let n = get_num_arg("number-format", &args[0])?;
+    let precision = get_precision_arg("number-format", &args[1])?;
+    Ok(Value::String(format!("{:.*}", precision, n)))
+}
+
+pub fn builtin_number_to_string(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_min_args("number->string", &args, 1)?;
+    if args.len() > 2 {
+        return Err(EvalError::WrongNumArgs(format!(
+            "number->string expects 1 or 2 arguments, but got {}",
+            args.len()
+        )));
+    }
+    let radix = match args.get(1) {
+        Some(radix) => get_int_arg("number->string", radix)?,
+        None => 10,
+    };
+    if radix == 10 {
+        // Printing the value drops the fractional part of whole numbers and spells infinities as +inf.0
+        let n = get_num_arg("number->string", &args[0])?;
+        return Ok(Value::String(Value::Number(n).to_string()));
+    }
+    if !(2..=36).contains(&radix) {
+        return Err(EvalError::TypeError("number->string expects a radix between 2 and 36".to_string()));
+    }
+
+    let n = get_int_arg("number->string", &args[0])?;
+    let mut magnitude = n.unsigned_abs();
+    let mut digits = Vec::new();
+    loop {
+        digits.push(std::char::from_digit((magnitude % radix as u64) as u32, radix as u32).unwrap_or('?'));
+        magnitude /= radix as u64;
+        if magnitude == 0 {
+            break;
+        }
+    }
+    if n < 0 {
+        digits.push('-');
+    }
+    Ok(Value::String(digits.into_iter().rev().collect()))
+}
+
+pub fn builtin_number_to_string_e(args: Vec<Value>) -> Result<Value, EvalError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(EvalError::WrongNumArgs(format!(
+            "number->string/e expects 1 or 2 arguments, but got {}",
+            args.len()
+        )));
+    }
+    let n = get_num_arg("number->string/e", &args[0])?;
+    let formatted = match args.get(1) {
+        Some(precision) => format!("{:.*e}", get_precision_arg("number->string/e", precision)?, n),
+        None => format!("{:e}", n),
+    };
+    Ok(Value::String(formatted))
+}
+
+struct NumberDirective {
+    width: usize,
+    precision: Option<usize>,
+    kind: char,
+}
+
+// Parses ~[width][,precision]kind where kind is d, f or e; "." may stand in for ",".
+// d takes no precision, and width and precision are capped at what format! accepts
+fn parse_number_directive(fmt: &str) -> Option<NumberDirective> {
+    let spec = fmt.strip_prefix('~')?;
+    let kind = spec.chars().last().filter(|kind| matches!(kind, 'd' | 'f' | 'e'))?;
+    let spec = &spec[..spec.len() - 1];
+
+    let (width, precision) = match spec.split_once([',', '.']) {
+        Some((width, precision)) => (width, Some(precision.parse().ok()?)),
+        None => (spec, None),
+    };
+    let width = if width.is_empty() { 0 } else { width.parse().ok()? };
+    if width > MAX_FORMAT_ARG || precision.is_some_and(|precision| precision > MAX_FORMAT_ARG) {
+        return None;
+    }
+    if kind == 'd' && precision.is_some() {
+        return None;
+    }
+    Some(NumberDirective { width, precision, kind })
+}
+
+pub fn builtin_string_format_number(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("string-format-number", &args, 2)?;
+    let n = get_num_arg("string-format-number", &args[0])?;
+    let fmt = get_string_arg("string-format-number", &args[1])?;
+    let directive = parse_number_directive(fmt).ok_or_else(|| {
+        EvalError::TypeError(format!("string-format-number: invalid format {}", args[1].write_form()))
+    })?;
+
+    let formatted = match (directive.kind, directive.precision) {
+        ('d', None) => format!("{:.0}", n),
+        ('f', Some(precision)) => format!("{:.*}", precision, n),
+        ('f', None) => Value::Number(n).to_string(),
+        (_, Some(precision)) => format!("{:.*e}", precision, n),
+        (_, None) => format!("{:e}", n),
+    };
+    Ok(Value::String(format!("{:>1$}", formatted, directive.width)))
+}
+
+pub fn builtin_random(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("random", &args, 1)?;
+    let limit = get_num_arg("random", &args[0])?;
+    if !limit.is_finite() || limit <= 0.0 {
+        return Err(EvalError::TypeError("random expects a positive finite number".to_string()));
+    }
+
+    let sample = next_random() * limit;
+    if limit.fract() == 0.0 {
+        Ok(Value::Number(sample.floor()))
+    } else {
+        Ok(Value::Number(sample))
+    }
+}
+
+pub fn builtin_random_seed(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("random-seed!", &args, 1)?;
+    let seed = get_num_arg("random-seed!", &args[0])?;
+    RANDOM_STATE.with(|state| state.set(seed as i64 as u64));
+    Ok(Value::Void)
+}
+
+pub fn builtin_current_time(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("current-time", &args, 0)?;
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| EvalError::TypeError(format!("current-time: system clock error: {}", e)))?;
+    Ok(Value::Number(elapsed.as_millis() as f64))
+}
+
+pub fn builtin_sleep(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("sleep", &args, 1)?;
+    let millis = get_num_arg("sleep", &args[0])?;
+    if millis < 0.0 || !millis.is_finite() {
+        return Err(EvalError::TypeError("sleep expects a non-negative number of milliseconds".to_string()));
+    }
+    let duration = Duration::try_from_secs_f64(millis / 1000.0)
+        .map_err(|_| EvalError::TypeError("sleep: duration is too long".to_string()))?;
+    thread::sleep(duration);
+    Ok(Value::Void)
+}
+
+pub fn builtin_flush_output(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("flush-output", &args, 0)?;
+    io::stdout()
+        .flush()
+        .map_err(|e| EvalError::IoError(format!("flush-output: {}", e)))?;
+    Ok(Value::Void)
+}
+
+// List functions
+pub fn builtin_list(args: Vec<Value>) -> Result<Value, EvalError> {
+    Ok(Value::list(args))
+}
+
+pub fn builtin_is_null(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("null?", &args, 1)?;
+    Ok(Value::Boolean(args[0] == Value::Nil))
+}
+
+pub fn builtin_is_pair(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("pair?", &args, 1)?;
+    Ok(Value::Boolean(matches!(args[0], Value::Pair(_))))
+}
+
+enum ListShape {
+    Proper,   // Ends in nil
+    Dotted,   // Ends in some other atom
+    Circular, // Never ends
+}
+
+fn cdr_of(value: &Value) -> Option<Value> {
+    match value {
+        Value::Pair(pair) => Some(pair.borrow().1.clone()),
+        _ => None,
+    }
+}
+
+// Walks the cdr chain with Floyd's tortoise and hare so cycles are detected without extra memory
+fn list_shape(value: &Value) -> ListShape {
+    let mut slow = value.clone();
+    let mut fast = value.clone();
+    loop {
+        for _ in 0..2 {
+            fast = match cdr_of(&fast) {
+                Some(next) => next,
+                None if fast == Value::Nil => return ListShape::Proper,
+                None => return ListShape::Dotted,
+            };
+        }
+        slow = cdr_of(&slow).unwrap_or(Value::Nil);
+        if let (Value::Pair(a), Value::Pair(b)) = (&slow, &fast)
+            && Rc::ptr_eq(a, b)
+        {
+            return ListShape::Circular;
+        }
+    }
+}
+
+pub fn builtin_proper_list_p(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("proper-list?", &args, 1)?;
+    Ok(Value::Boolean(matches!(list_shape(&args[0]), ListShape::Proper)))
+}
+
+pub fn builtin_dotted_list_p(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("dotted-list?", &args, 1)?;
+    Ok(Value::Boolean(matches!(list_shape(&args[0]), ListShape::Dotted)))
+}
+
+pub fn builtin_circular_list_p(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("circular-list?", &args, 1)?;
+    Ok(Value::Boolean(matches!(list_shape(&args[0]), ListShape::Circular)))
+}
+
+pub fn builtin_cons(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("cons", &args, 2)?;
+    let mut args = args.into_iter();
+    let car = args.next().unwrap_or(Value::Nil);
+    let cdr = args.next().unwrap_or(Value::Nil);
+    Ok(Value::cons(car, cdr))
+}
+
+pub fn builtin_car(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("car", &args, 1)?;
+    match &args[0] {
+        Value::Pair(pair) => Ok(pair.borrow().0.clone()),
+        _ => Err(EvalError::TypeError("car expects a pair".to_string())),
+    }
+}
+
+pub fn builtin_cdr(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("cdr", &args, 1)?;
+    match &args[0] {
+        Value::Pair(pair) => Ok(pair.borrow().1.clone()),
+        _ => Err(EvalError::TypeError("cdr expects a pair".to_string())),
+    }
+}
+
+pub fn builtin_set_car(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("set-car!", &args, 2)?;
+    match &args[0] {
+        Value::Pair(pair) => {
+            pair.borrow_mut().0 = args[1].clone();
+            Ok(Value::Void)
+        }
+        _ => Err(EvalError::TypeError("set-car! expects a pair".to_string())),
+    }
+}
+
+pub fn builtin_set_cdr(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("set-cdr!", &args, 2)?;
+    match &args[0] {
+        Value::Pair(pair) => {
+            pair.borrow_mut().1 = args[1].clone();
+            Ok(Value::Void)
+        }
+        _ => Err(EvalError::TypeError("set-cdr! expects a pair".to_string())),
+    }
+}
+
+pub fn builtin_zip(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_min_args("zip", &args, 1)?;
+    let lists = args
+        .iter()
+        .map(|arg| get_list_arg("zip", arg))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Stops at the shortest list
+    let len = lists.iter().map(Vec::len).min().unwrap_or(0);
+    let tuples = (0..len)
+        .map(|i| Value::list(lists.iter().map(|list| list[i].clone()).collect()))
+        .collect();
+    Ok(Value::list(tuples))
+}
+
+pub fn builtin_unzip1(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("unzip1", &args, 1)?;
+    let firsts = get_list_arg("unzip1", &args[0])?
+        .into_iter()
+        .map(|tuple| match tuple {
+            Value::Pair(pair) => Ok(pair.borrow().0.clone()),
+            _ => Err(EvalError::TypeError("unzip1 expects a list of lists".to_string())),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Value::list(firsts))
+}
+
+pub fn builtin_last(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("last", &args, 1)?;
+    let mut items = get_list_arg("last", &args[0])?;
+    items
+        .pop()
+        .ok_or_else(|| EvalError::TypeError("last expects a non-empty list".to_string()))
+}
+
+pub fn builtin_but_last(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("but-last", &args, 1)?;
+    let mut items = get_list_arg("but-last", &args[0])?;
+    if items.pop().is_none() {
+        return Err(EvalError::TypeError("but-last expects a non-empty list".to_string()));
+    }
+    Ok(Value::list(items))
+}
+
+pub fn builtin_concatenate(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("concatenate", &args, 1)?;
+    let mut items = Vec::new();
+    for list in get_list_arg("concatenate", &args[0])? {
+        items.extend(get_list_arg("concatenate", &list)?);
+    }
+    Ok(Value::list(items))
+}
+
+// (check-list-lengths who lists) fails unless all the lists are equally long. The standard library's
+// multi-list procedures call it first, since R7RS leaves unequal lengths an error rather than truncating
+pub fn builtin_check_list_lengths(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("check-list-lengths", &args, 2)?;
+    let who = get_string_arg("check-list-lengths", &args[0])?;
+    let mut lengths = Vec::new();
+    for list in get_list_arg("check-list-lengths", &args[1])? {
+        lengths.push(get_list_arg(who, &list)?.len());
+    }
+    if lengths.windows(2).any(|pair| pair[0] != pair[1]) {
+        return Err(EvalError::WrongNumArgs(format!("{}: lists have different lengths", who)));
+    }
+    Ok(Value::Void)
+}
+
+// Copies every pair and mutable buffer reachable from the value; atoms are shared since they cannot be mutated.
+// Copies are remembered by the address of their original, so shared structure stays shared and cycles stay cycles.
+#[derive(Default)]
+struct DeepCopier {
+    copies: HashMap<*const (), Value>,
+}
+
+impl DeepCopier {
+    fn copy(&mut self, value: &Value) -> Value {
+        let key = match value {
+            Value::Pair(pair) => Rc::as_ptr(pair) as *const (),
+            Value::Bytevector(bytes) => Rc::as_ptr(bytes) as *const (),
+            Value::StringBuilder(buffer) => Rc::as_ptr(buffer) as *const (),
+            other => return other.clone(),
+        };
+        if let Some(copy) = self.copies.get(&key) {
+            return copy.clone();
+        }
+        match value {
+            Value::Pair(pair) => self.copy_pairs(pair),
+            Value::Bytevector(bytes) => self.remember(key, Value::Bytevector(Rc::new(RefCell::new(bytes.borrow().clone())))),
+            Value::StringBuilder(buffer) => self.remember(key, Value::StringBuilder(Rc::new(RefCell::new(buffer.borrow().clone())))),
+            _ => unreachable!(),
+        }
+    }
+
+    fn remember(&mut self, key: *const (), copy: Value) -> Value {
+        self.copies.insert(key, copy.clone());
+        copy
+    }
+
+    // Walks the cdr chain iteratively so long lists don't recurse; each new pair is registered
+    // before its car is copied so that references back to it resolve to the copy
+    fn copy_pairs(&mut self, first: &Rc<RefCell<(Value, Value)>>) -> Value {
+        let head = Rc::new(RefCell::new((Value::Nil, Value::Nil)));
+        self.remember(Rc::as_ptr(first) as *const (), Value::Pair(head.clone()));
+        let mut source = first.clone();
+        let mut target = head.clone();
+        loop {
+            let (car, cdr) = source.borrow().clone();
+            let car = self.copy(&car);
+            target.borrow_mut().0 = car;
+            match cdr {
+                Value::Pair(next) if !self.copies.contains_key(&(Rc::as_ptr(&next) as *const ())) => {
+                    let copy = Rc::new(RefCell::new((Value::Nil, Value::Nil)));
+                    self.remember(Rc::as_ptr(&next) as *const (), Value::Pair(copy.clone()));
+                    target.borrow_mut().1 = Value::Pair(copy.clone());
+                    source = next;
+                    target = copy;
+                }
+                other => {
+                    let tail = self.copy(&other);
+                    target.borrow_mut().1 = tail;
+                    break;
+                }
+            }
+        }
+        Value::Pair(head)
+    }
+}
+
+pub fn builtin_deep_copy(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("deep-copy", &args, 1)?;
+    Ok(DeepCopier::default().copy(&args[0]))
+}
+
+// Every element is a separate pair of a couple of hundred bytes
+const MAX_LIST_LENGTH: usize = 1 << 20;
+
+pub fn builtin_make_list(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_min_args("make-list", &args, 1)?;
+    if args.len() > 2 {
+        return Err(EvalError::WrongNumArgs(format!(
+            "make-list expects 1 or 2 arguments, but got {}",
+            args.len()
+        )));
+    }
+    let count = get_count_arg("make-list", &args[0], MAX_LIST_LENGTH)?;
+    let fill = args.get(1).cloned().unwrap_or(Value::Nil);
+    Ok(Value::list(vec![fill; count]))
+}
+
+pub fn builtin_list_fill(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("list-fill!", &args, 2)?;
+    let mut current = args[0].clone();
+    loop {
+        let next = match &current {
+            Value::Nil => return Ok(Value::Void),
+            Value::Pair(pair) => {
+                let mut cell = pair.borrow_mut();
+                cell.0 = args[1].clone();
+                cell.1.clone()
+            }
+            _ => {
+                return Err(EvalError::TypeError("list-fill! expects a proper list".to_string()));
+            }
+        };
+        current = next;
+    }
+}
+
+fn count_matching(evaluator: &Evaluator, func_name: &str, args: Vec<Value>, wanted: bool) -> Result<Value, EvalError> {
+    check_num_args(func_name, &args, 2)?;
+    let items = get_list_arg(func_name, &args[1])?;
+    let pred = args.into_iter().next().unwrap_or(Value::Nil);
+
+    let mut count = 0;
+    for item in items {
+        if is_truthy(&evaluator.apply(pred.clone(), vec![item])?) == wanted {
+            count += 1;
         }
     }
-    println!();
-    Ok(Value::Nil)
-}
\ No newline at end of file
+    Ok(Value::Number(count as f64))
+}
+
+pub fn builtin_count(evaluator: &Evaluator, args: Vec<Value>) -> Result<Value, EvalError> {
+    count_matching(evaluator, "count", args, true)
+}
+
+pub fn builtin_count_if_not(evaluator: &Evaluator, args: Vec<Value>) -> Result<Value, EvalError> {
+    count_matching(evaluator, "count-if-not", args, false)
+}
+
+// As in SRFI-1, f is called as (f element accumulator) and init is only returned for an empty list
+pub fn builtin_reduce(evaluator: &Evaluator, args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("reduce", &args, 3)?;
+    let items = get_list_arg("reduce", &args[2])?;
+    let mut args = args.into_iter();
+    let func = args.next().unwrap_or(Value::Nil);
+    let init = args.next().unwrap_or(Value::Nil);
+
+    let mut items = items.into_iter();
+    let Some(mut acc) = items.next() else {
+        return Ok(init);
+    };
+    for item in items {
+        acc = evaluator.apply(func.clone(), vec![item, acc])?;
+    }
+    Ok(acc)
+}
+
+// Folds from the right: (reduce-right f init (a b c)) is (f a (f b c))
+pub fn builtin_reduce_right(evaluator: &Evaluator, args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("reduce-right", &args, 3)?;
+    let items = get_list_arg("reduce-right", &args[2])?;
+    let mut args = args.into_iter();
+    let func = args.next().unwrap_or(Value::Nil);
+    let init = args.next().unwrap_or(Value::Nil);
+
+    let mut items = items.into_iter().rev();
+    let Some(mut acc) = items.next() else {
+        return Ok(init);
+    };
+    for item in items {
+        acc = evaluator.apply(func.clone(), vec![item, acc])?;
+    }
+    Ok(acc)
+}
+
+// String functions
+pub fn builtin_string_split(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("string-split", &args, 2)?;
+    let s = get_string_arg("string-split", &args[0])?;
+    let separator = get_string_arg("string-split", &args[1])?;
+
+    let parts: Vec<Value> = if separator.is_empty() {
+        s.chars().map(|c| Value::String(c.to_string())).collect()
+    } else {
+        s.split(separator).map(|part| Value::String(part.to_string())).collect()
+    };
+    Ok(Value::list(parts))
+}
+
+pub fn builtin_string_join(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("string-join", &args, 2)?;
+    let items = get_list_arg("string-join", &args[0])?;
+    let separator = get_string_arg("string-join", &args[1])?;
+
+    let parts = items
+        .iter()
+        .map(|item| get_string_arg("string-join", item))
+        .collect::<Result<Vec<&str>, EvalError>>()?;
+    Ok(Value::String(parts.join(separator)))
+}
+
+pub fn builtin_string_trim(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("string-trim", &args, 1)?;
+    let s = get_string_arg("string-trim", &args[0])?;
+    Ok(Value::String(s.trim_start().to_string()))
+}
+
+pub fn builtin_string_trim_right(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("string-trim-right", &args, 1)?;
+    let s = get_string_arg("string-trim-right", &args[0])?;
+    Ok(Value::String(s.trim_end().to_string()))
+}
+
+pub fn builtin_string_trim_both(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("string-trim-both", &args, 1)?;
+    let s = get_string_arg("string-trim-both", &args[0])?;
+    Ok(Value::String(s.trim().to_string()))
+}
+
+pub fn builtin_string_prefix_p(args: Vec<Value>) -> Result<Value, EvalError> {
+    let (prefix, s) = get_two_string_args("string-prefix?", &args)?;
+    Ok(Value::Boolean(s.starts_with(prefix)))
+}
+
+pub fn builtin_string_suffix_p(args: Vec<Value>) -> Result<Value, EvalError> {
+    let (suffix, s) = get_two_string_args("string-suffix?", &args)?;
+    Ok(Value::Boolean(s.ends_with(suffix)))
+}
+
+pub fn builtin_string_prefix_ci_p(args: Vec<Value>) -> Result<Value, EvalError> {
+    let (prefix, s) = get_two_string_args("string-prefix-ci?", &args)?;
+    Ok(Value::Boolean(s.to_lowercase().starts_with(&prefix.to_lowercase())))
+}
+
+pub fn builtin_string_suffix_ci_p(args: Vec<Value>) -> Result<Value, EvalError> {
+    let (suffix, s) = get_two_string_args("string-suffix-ci?", &args)?;
+    Ok(Value::Boolean(s.to_lowercase().ends_with(&suffix.to_lowercase())))
+}
+
+pub fn builtin_string_ci_eq(args: Vec<Value>) -> Result<Value, EvalError> {
+    let (a, b) = get_two_string_args("string-ci=?", &args)?;
+    Ok(Value::Boolean(a.to_lowercase() == b.to_lowercase()))
+}
+
+pub fn builtin_string_ci_lt(args: Vec<Value>) -> Result<Value, EvalError> {
+    let (a, b) = get_two_string_args("string-ci<?", &args)?;
+    Ok(Value::Boolean(a.to_lowercase() < b.to_lowercase()))
+}
+
+pub fn builtin_string_ci_gt(args: Vec<Value>) -> Result<Value, EvalError> {
+    let (a, b) = get_two_string_args("string-ci>?", &args)?;
+    Ok(Value::Boolean(a.to_lowercase() > b.to_lowercase()))
+}
+
+pub fn builtin_string_ci_le(args: Vec<Value>) -> Result<Value, EvalError> {
+    let (a, b) = get_two_string_args("string-ci<=?", &args)?;
+    Ok(Value::Boolean(a.to_lowercase() <= b.to_lowercase()))
+}
+
+pub fn builtin_string_ci_ge(args: Vec<Value>) -> Result<Value, EvalError> {
+    let (a, b) = get_two_string_args("string-ci>=?", &args)?;
+    Ok(Value::Boolean(a.to_lowercase() >= b.to_lowercase()))
+}
+
+pub fn builtin_string_replace(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("string-replace", &args, 3)?;
+    let s = get_string_arg("string-replace", &args[0])?;
+    let from = get_string_arg("string-replace", &args[1])?;
+    let to = get_string_arg("string-replace", &args[2])?;
+    Ok(Value::String(s.replace(from, to)))
+}
+
+pub fn builtin_string_replace_first(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("string-replace-first", &args, 3)?;
+    let s = get_string_arg("string-replace-first", &args[0])?;
+    let from = get_string_arg("string-replace-first", &args[1])?;
+    let to = get_string_arg("string-replace-first", &args[2])?;
+    Ok(Value::String(s.replacen(from, to, 1)))
+}
+
+pub fn builtin_string_copy(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("string-copy", &args, 1)?;
+    let s = get_string_arg("string-copy", &args[0])?;
+    Ok(Value::String(s.to_string()))
+}
+
+fn get_string_builder_arg<'a>(func_name: &str, arg: &'a Value) -> Result<&'a Rc<RefCell<String>>, EvalError> {
+    match arg {
+        Value::StringBuilder(buffer) => Ok(buffer),
+        _ => Err(EvalError::TypeError(format!("{} expects a string builder", func_name))),
+    }
+}
+
+pub fn builtin_make_string_builder(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("make-string-builder", &args, 0)?;
+    Ok(Value::StringBuilder(Rc::new(RefCell::new(String::new()))))
+}
+
+// Appends in place, so building a string piece by piece stays linear instead of copying on every string-append
+pub fn builtin_string_builder_append(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_min_args("string-builder-append!", &args, 1)?;
+    let buffer = get_string_builder_arg("string-builder-append!", &args[0])?;
+    for arg in &args[1..] {
+        let s = get_string_arg("string-builder-append!", arg)?;
+        buffer.borrow_mut().push_str(s);
+    }
+    Ok(Value::Void)
+}
+
+pub fn builtin_string_builder_to_string(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("string-builder->string", &args, 1)?;
+    let buffer = get_string_builder_arg("string-builder->string", &args[0])?;
+    Ok(Value::String(buffer.borrow().clone()))
+}
+
+// Bitwise functions
+pub fn builtin_bitwise_and(args: Vec<Value>) -> Result<Value, EvalError> {
+    let numbers = get_all_int_args("bitwise-and", &args)?;
+    Ok(Value::Number(numbers.into_iter().fold(-1, |acc, n| acc & n) as f64))
+}
+
+pub fn builtin_bitwise_or(args: Vec<Value>) -> Result<Value, EvalError> {
+    let numbers = get_all_int_args("bitwise-or", &args)?;
+    Ok(Value::Number(numbers.into_iter().fold(0, |acc, n| acc | n) as f64))
+}
+
+pub fn builtin_bitwise_xor(args: Vec<Value>) -> Result<Value, EvalError> {
+    let numbers = get_all_int_args("bitwise-xor", &args)?;
+    Ok(Value::Number(numbers.into_iter().fold(0, |acc, n| acc ^ n) as f64))
+}
+
+pub fn builtin_bitwise_not(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("bitwise-not", &args, 1)?;
+    let n = get_int_arg("bitwise-not", &args[0])?;
+    Ok(Value::Number(!n as f64))
+}
+
+pub fn builtin_arithmetic_shift(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("arithmetic-shift", &args, 2)?;
+    let n = get_int_arg("arithmetic-shift", &args[0])?;
+    let shift = get_int_arg("arithmetic-shift", &args[1])?;
+
+    let shifted = if shift >= 0 {
+        // Bits pushed into or past the sign bit are lost, which shifting back reveals
+        match u32::try_from(shift).ok().filter(|&shift| shift < i64::BITS) {
+            _ if n == 0 => 0,
+            Some(shift) if (n << shift) >> shift == n => n << shift,
+            _ => return Err(EvalError::TypeError("arithmetic-shift overflows 64 bits".to_string())),
+        }
+    } else {
+        // Shifting right by the full width leaves only the sign bits
+        n >> shift.unsigned_abs().min(63)
+    };
+    Ok(Value::Number(shifted as f64))
+}
+
+pub fn builtin_bit_count(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("bit-count", &args, 1)?;
+    let n = get_int_arg("bit-count", &args[0])?;
+    // Negative numbers count their zero bits, as in SRFI-151
+    let count = if n >= 0 { n.count_ones() } else { n.count_zeros() };
+    Ok(Value::Number(count as f64))
+}
+
+// Bytevector functions
+fn get_byte_arg(func_name: &str, arg: &Value) -> Result<u8, EvalError> {
+    let n = get_int_arg(func_name, arg)?;
+    u8::try_from(n).map_err(|_| EvalError::TypeError(format!("{} expects bytes between 0 and 255", func_name)))
+}
+
+fn get_bytevector_arg<'a>(func_name: &str, arg: &'a Value) -> Result<&'a Rc<RefCell<Vec<u8>>>, EvalError> {
+    match arg {
+        Value::Bytevector(bytes) => Ok(bytes),
+        _ => Err(EvalError::TypeError(format!("{} expects a bytevector", func_name))),
+    }
+}
+
+fn get_index_arg(func_name: &str, arg: &Value, len: usize) -> Result<usize, EvalError> {
+    let index = get_int_arg(func_name, arg)?;
+    usize::try_from(index)
+        .ok()
+        .filter(|index| *index < len)
+        .ok_or_else(|| EvalError::TypeError(format!("{}: index {} out of range", func_name, index)))
+}
+
+const MAX_BYTEVECTOR_LENGTH: usize = 1 << 30;
+
+pub fn builtin_make_bytevector(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_min_args("make-bytevector", &args, 1)?;
+    if args.len() > 2 {
+        return Err(EvalError::WrongNumArgs(format!(
+            "make-bytevector expects 1 or 2 arguments, but got {}",
+            args.len()
+        )));
+    }
+    let count = get_count_arg("make-bytevector", &args[0], MAX_BYTEVECTOR_LENGTH)?;
+    let fill = match args.get(1) {
+        Some(fill) => get_byte_arg("make-bytevector", fill)?,
+        None => 0,
+    };
+    Ok(Value::Bytevector(Rc::new(RefCell::new(vec![fill; count]))))
+}
+
+pub fn builtin_bytevector(args: Vec<Value>) -> Result<Value, EvalError> {
+    let bytes = args
+        .iter()
+        .map(|arg| get_byte_arg("bytevector", arg))
+        .collect::<Result<Vec<u8>, EvalError>>()?;
+    Ok(Value::Bytevector(Rc::new(RefCell::new(bytes))))
+}
+
+pub fn builtin_is_bytevector(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("bytevector?", &args, 1)?;
+    Ok(Value::Boolean(matches!(args[0], Value::Bytevector(_))))
+}
+
+pub fn builtin_bytevector_length(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("bytevector-length", &args, 1)?;
+    let bytes = get_bytevector_arg("bytevector-length", &args[0])?;
+    Ok(Value::Number(bytes.borrow().len() as f64))
+}
+
+pub fn builtin_bytevector_u8_ref(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("bytevector-u8-ref", &args, 2)?;
+    let bytes = get_bytevector_arg("bytevector-u8-ref", &args[0])?.borrow();
+    let index = get_index_arg("bytevector-u8-ref", &args[1], bytes.len())?;
+    Ok(Value::Number(bytes[index] as f64))
+}
+
+pub fn builtin_bytevector_u8_set(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("bytevector-u8-set!", &args, 3)?;
+    let mut bytes = get_bytevector_arg("bytevector-u8-set!", &args[0])?.borrow_mut();
+    let index = get_index_arg("bytevector-u8-set!", &args[1], bytes.len())?;
+    bytes[index] = get_byte_arg("bytevector-u8-set!", &args[2])?;
+    Ok(Value::Void)
+}
+
+// Reads the optional [start [end]] arguments that select part of a sequence of length len
+fn get_range_args(func_name: &str, args: &[Value], len: usize) -> Result<(usize, usize), EvalError> {
+    let bound = |index: usize, default: usize| -> Result<usize, EvalError> {
+        match args.get(index) {
+            Some(arg) => {
+                let n = get_int_arg(func_name, arg)?;
+                usize::try_from(n)
+                    .ok()
+                    .filter(|n| *n <= len)
+                    .ok_or_else(|| EvalError::TypeError(format!("{}: index {} out of range", func_name, n)))
+            }
+            None => Ok(default),
+        }
+    };
+    let start = bound(0, 0)?;
+    let end = bound(1, len)?;
+    if start > end {
+        return Err(EvalError::TypeError(format!("{}: start {} is after end {}", func_name, start, end)));
+    }
+    Ok((start, end))
+}
+
+fn check_range_arity(func_name: &str, args: &[Value]) -> Result<(), EvalError> {
+    check_min_args(func_name, args, 1)?;
+    if args.len() > 3 {
+        return Err(EvalError::WrongNumArgs(format!(
+            "{} expects 1 to 3 arguments, but got {}",
+            func_name,
+            args.len()
+        )));
+    }
+    Ok(())
+}
+
+pub fn builtin_string_to_utf8(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_range_arity("string->utf8", &args)?;
+    let s = get_string_arg("string->utf8", &args[0])?;
+    // Start and end count characters, not bytes
+    let chars: Vec<char> = s.chars().collect();
+    let (start, end) = get_range_args("string->utf8", &args[1..], chars.len())?;
+    let bytes = chars[start..end].iter().collect::<String>().into_bytes();
+    Ok(Value::Bytevector(Rc::new(RefCell::new(bytes))))
+}
+
+pub fn builtin_utf8_to_string(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_range_arity("utf8->string", &args)?;
+    let bytes = get_bytevector_arg("utf8->string", &args[0])?.borrow();
+    let (start, end) = get_range_args("utf8->string", &args[1..], bytes.len())?;
+    match std::str::from_utf8(&bytes[start..end]) {
+        Ok(s) => Ok(Value::String(s.to_string())),
+        Err(e) => Err(EvalError::TypeError(format!("utf8->string: invalid UTF-8 ({})", e))),
+    }
+}
+
+// Reader and output capture
+pub fn builtin_read_from_string(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("read-from-string", &args, 1)?;
+    let source = get_string_arg("read-from-string", &args[0])?;
+
+    let datum = Parser::new(Tokenizer::new(source))
+        .parse_one()
+        .map_err(|e| EvalError::ReadError(e.to_string()))?;
+
+    match datum {
+        Some(expr) => Ok(Value::from_expression(&expr)),
+        None => Ok(Value::Eof),
+    }
+}
+
+pub fn builtin_eof_object(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("eof-object", &args, 0)?;
+    Ok(Value::Eof)
+}
+
+pub fn builtin_is_eof(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("eof-object?", &args, 1)?;
+    Ok(Value::Boolean(args[0] == Value::Eof))
+}
+
+fn get_environment_arg(func_name: &str, arg: &Value) -> Result<Rc<RefCell<Environment>>, EvalError> {
+    match arg {
+        Value::EnvironmentRef(env) => Ok(Rc::clone(env)),
+        _ => Err(EvalError::TypeError(format!("{} expects an environment", func_name))),
+    }
+}
+
+pub fn builtin_eval(evaluator: &Evaluator, args: Vec<Value>) -> Result<Value, EvalError> {
+    check_min_args("eval", &args, 1)?;
+    if args.len() > 2 {
+        return Err(EvalError::WrongNumArgs(format!(
+            "eval expects 1 or 2 arguments, but got {}",
+            args.len()
+        )));
+    }
+    let expr = args[0].to_expression()?;
+    let env = match args.get(1) {
+        Some(env) => get_environment_arg("eval", env)?,
+        None => Rc::clone(&evaluator.global_env),
+    };
+    evaluator.evaluate(&expr, env)
+}
+
+pub fn builtin_interaction_environment(evaluator: &Evaluator, args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("interaction-environment", &args, 0)?;
+    Ok(Value::EnvironmentRef(Rc::clone(&evaluator.global_env)))
+}
+
+pub fn builtin_environment_ref(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("environment-ref", &args, 2)?;
+    let env = get_environment_arg("environment-ref", &args[0])?;
+    let name = get_name_arg("environment-ref", &args[1])?;
+    let value = env.borrow().get(name)?;
+    Ok(value)
+}
+
+pub fn builtin_with_output_to_string(evaluator: &Evaluator, args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("with-output-to-string", &args, 1)?;
+    let thunk = args.into_iter().next().unwrap_or(Value::Nil);
+
+    OUTPUT_CAPTURE.with(|capture| capture.borrow_mut().push(String::new()));
+    let result = evaluator.apply(thunk, Vec::new());
+    let captured = OUTPUT_CAPTURE
+        .with(|capture| capture.borrow_mut().pop())
+        .unwrap_or_default();
+
+    result.map(|_| Value::String(captured))
+}
+
+// File system functions
+pub fn builtin_file_exists_p(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("file-exists?", &args, 1)?;
+    let path = get_string_arg("file-exists?", &args[0])?;
+    Ok(Value::Boolean(Path::new(path).exists()))
+}
+
+pub fn builtin_delete_file(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("delete-file", &args, 1)?;
+    let path = get_string_arg("delete-file", &args[0])?;
+    fs::remove_file(path)
+        .map_err(|e| EvalError::IoError(format!("delete-file '{}': {}", path, e)))?;
+    Ok(Value::Void)
+}
+
+pub fn builtin_rename_file(args: Vec<Value>) -> Result<Value, EvalError> {
+    let (from, to) = get_two_string_args("rename-file", &args)?;
+    fs::rename(from, to)
+        .map_err(|e| EvalError::IoError(format!("rename-file '{}' to '{}': {}", from, to, e)))?;
+    Ok(Value::Void)
+}
+
+pub fn builtin_directory_list(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("directory-list", &args, 1)?;
+    let path = get_string_arg("directory-list", &args[0])?;
+    let io_error = |e: std::io::Error| EvalError::IoError(format!("directory-list '{}': {}", path, e));
+
+    let mut names = Vec::new();
+    for entry in fs::read_dir(path).map_err(io_error)? {
+        let entry = entry.map_err(io_error)?;
+        names.push(entry.file_name().to_string_lossy().into_owned());
+    }
+    names.sort();
+    Ok(Value::list(names.into_iter().map(Value::String).collect()))
+}
+
+pub fn builtin_make_directory(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("make-directory", &args, 1)?;
+    let path = get_string_arg("make-directory", &args[0])?;
+    fs::create_dir(path)
+        .map_err(|e| EvalError::IoError(format!("make-directory '{}': {}", path, e)))?;
+    Ok(Value::Void)
+}
+
+pub fn builtin_read_file(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("read-file", &args, 1)?;
+    let path = get_string_arg("read-file", &args[0])?;
+    let contents = fs::read_to_string(path)
+        .map_err(|e| EvalError::IoError(format!("read-file '{}': {}", path, e)))?;
+    Ok(Value::String(contents))
+}
+
+pub fn builtin_write_file(args: Vec<Value>) -> Result<Value, EvalError> {
+    let (path, contents) = get_two_string_args("write-file", &args)?;
+    fs::write(path, contents)
+        .map_err(|e| EvalError::IoError(format!("write-file '{}': {}", path, e)))?;
+    Ok(Value::Void)
+}
+
+// Error objects
+pub fn builtin_error(evaluator: &Evaluator, args: Vec<Value>) -> Result<Value, EvalError> {
+    check_min_args("error", &args, 1)?;
+    let message = get_string_arg("error", &args[0])?.to_string();
+    let irritants = args[1..].to_vec();
+    Err(signal_raise(evaluator, Value::ErrorObject { message, irritants }))
+}
+
+pub fn builtin_is_error_object(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("error-object?", &args, 1)?;
+    Ok(Value::Boolean(matches!(args[0], Value::ErrorObject { .. })))
+}
+
+pub fn builtin_error_message(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("error-message", &args, 1)?;
+    match &args[0] {
+        Value::ErrorObject { message, .. } => Ok(Value::String(message.clone())),
+        _ => Err(EvalError::TypeError("error-message expects an error object".to_string())),
+    }
+}
+
+pub fn builtin_error_irritants(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("error-irritants", &args, 1)?;
+    match &args[0] {
+        Value::ErrorObject { irritants, .. } => Ok(Value::list(irritants.clone())),
+        _ => Err(EvalError::TypeError("error-irritants expects an error object".to_string())),
+    }
+}
+
+// Conditions
+fn get_name_arg<'a>(func_name: &str, arg: &'a Value) -> Result<&'a str, EvalError> {
+    match arg {
+        Value::Symbol(name) | Value::String(name) => Ok(name),
+        _ => Err(EvalError::TypeError(format!("{} expects a symbol or a name string", func_name))),
+    }
+}
+
+pub fn builtin_make_condition(evaluator: &Evaluator, args: Vec<Value>) -> Result<Value, EvalError> {
+    check_min_args("make-condition", &args, 2)?;
+    let type_name = get_name_arg("make-condition", &args[0])?.to_string();
+    let message = get_string_arg("make-condition", &args[1])?.to_string();
+    let Some(declared_fields) = evaluator.condition_type_fields(&type_name) else {
+        return Err(EvalError::TypeError(format!("make-condition: unknown condition type {}", type_name)));
+    };
+
+    let field_args = &args[2..];
+    if !field_args.len().is_multiple_of(2) {
+        return Err(EvalError::WrongNumArgs(
+            "make-condition expects field names and values in pairs".to_string(),
+        ));
+    }
+    let mut fields = HashMap::new();
+    for pair in field_args.chunks(2) {
+        let field_name = get_name_arg("make-condition", &pair[0])?;
+        if !declared_fields.iter().any(|declared| declared == field_name) {
+            return Err(EvalError::TypeError(format!(
+                "make-condition: {} has no field {}",
+                type_name, field_name
+            )));
+        }
+        fields.insert(field_name.to_string(), pair[1].clone());
+    }
+
+    Ok(Value::Condition { type_name, message, fields })
+}
+
+pub fn builtin_is_condition(args: Vec<Value>) -> Result<Value, EvalError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(EvalError::WrongNumArgs(format!(
+            "condition? expects 1 or 2 arguments, but got {}",
+            args.len()
+        )));
+    }
+    let Value::Condition { type_name, .. } = &args[0] else {
+        return Ok(Value::Boolean(false));
+    };
+    match args.get(1) {
+        Some(expected) => Ok(Value::Boolean(type_name == get_name_arg("condition?", expected)?)),
+        None => Ok(Value::Boolean(true)),
+    }
+}
+
+pub fn builtin_condition_ref(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("condition/ref", &args, 2)?;
+    let Value::Condition { type_name, fields, .. } = &args[0] else {
+        return Err(EvalError::TypeError("condition/ref expects a condition".to_string()));
+    };
+    let field_name = get_name_arg("condition/ref", &args[1])?;
+    fields.get(field_name).cloned().ok_or_else(|| {
+        EvalError::TypeError(format!("condition/ref: {} has no value for field {}", type_name, field_name))
+    })
+}
+
+pub fn builtin_condition_type_name(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("condition-type/name", &args, 1)?;
+    match &args[0] {
+        Value::Condition { type_name, .. } => Ok(Value::String(type_name.clone())),
+        _ => Err(EvalError::TypeError("condition-type/name expects a condition".to_string())),
+    }
+}
+
+pub fn builtin_condition_message(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("condition/message", &args, 1)?;
+    match &args[0] {
+        Value::Condition { message, .. } => Ok(Value::String(message.clone())),
+        _ => Err(EvalError::TypeError("condition/message expects a condition".to_string())),
+    }
+}
+
+// Exceptions
+
+// Runs the handlers of a non-continuable raise at the point of the raise, before anything unwinds.
+// Each handler runs with only the handlers outside it installed; one that returns passes the object
+// on to the next, as R7RS's secondary exception does. The error to unwind with is returned.
+fn signal_raise(evaluator: &Evaluator, obj: Value) -> EvalError {
+    let handlers = EXCEPTION_HANDLERS.with(|installed| installed.borrow().clone());
+    let mut outcome = EvalError::Raised(obj.clone());
+    for (index, handler) in handlers.iter().enumerate().rev() {
+        EXCEPTION_HANDLERS.with(|installed| installed.borrow_mut().truncate(index));
+        if let Err(error) = evaluator.apply(handler.clone(), vec![obj.clone()]) {
+            outcome = error;
+            break;
+        }
+    }
+    EXCEPTION_HANDLERS.with(|installed| *installed.borrow_mut() = handlers);
+    outcome
+}
+
+// Evaluates with no handlers installed, for forms like ignore-errors that catch every error themselves
+pub(crate) fn without_exception_handlers<T>(f: impl FnOnce() -> T) -> T {
+    let handlers = EXCEPTION_HANDLERS.with(|installed| installed.take());
+    let result = f();
+    EXCEPTION_HANDLERS.with(|installed| *installed.borrow_mut() = handlers);
+    result
+}
+
+pub fn builtin_raise(evaluator: &Evaluator, args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("raise", &args, 1)?;
+    let obj = args.into_iter().next().unwrap_or(Value::Nil);
+    Err(signal_raise(evaluator, obj))
+}
+
+pub fn builtin_raise_continuable(evaluator: &Evaluator, args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("raise-continuable", &args, 1)?;
+    let obj = args.into_iter().next().unwrap_or(Value::Nil);
+
+    // The handler runs with the outer handlers installed, and its result is returned to the raiser
+    let Some(handler) = EXCEPTION_HANDLERS.with(|handlers| handlers.borrow_mut().pop()) else {
+        return Err(EvalError::Raised(obj));
+    };
+    let result = evaluator.apply(handler.clone(), vec![obj]);
+    EXCEPTION_HANDLERS.with(|handlers| handlers.borrow_mut().push(handler));
+    result
+}
+
+pub fn builtin_with_exception_handler(evaluator: &Evaluator, args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("with-exception-handler", &args, 2)?;
+    let mut args = args.into_iter();
+    let handler = args.next().unwrap_or(Value::Nil);
+    let thunk = args.next().unwrap_or(Value::Nil);
+
+    EXCEPTION_HANDLERS.with(|handlers| handlers.borrow_mut().push(handler.clone()));
+    let result = evaluator.apply(thunk, Vec::new());
+    EXCEPTION_HANDLERS.with(|handlers| handlers.borrow_mut().pop());
+
+    // Raised values already met their handlers at the raise; escapes are control flow, and the
+    // depth limit must not be re-entered by a handler
+    let error = match result {
+        Err(EvalError::Raised(_) | EvalError::Escape(_, _) | EvalError::StackOverflow) | Ok(_) => return result,
+        Err(error) => error,
+    };
+
+    // Errors detected by the interpreter itself cannot stop to call a handler, so the handler sees
+    // them here, after unwinding. If it returns, the error keeps propagating.
+    let obj = Value::ErrorObject {
+        message: error.to_string(),
+        irritants: Vec::new(),
+    };
+    evaluator.apply(handler, vec![obj])?;
+    Err(error)
+}
+
+// Debugging
+fn get_traced_name(func_name: &str, arg: &Value) -> Result<String, EvalError> {
+    match arg {
+        Value::Function(callable) => match &**callable {
+            Callable::Lambda { name: Some(name), .. } => Ok(name.clone()),
+            _ => Err(EvalError::TypeError(format!("{} expects a named lambda", func_name))),
+        },
+        Value::String(name) => Ok(name.clone()),
+        _ => Err(EvalError::TypeError(format!("{} expects a named lambda or a name string", func_name))),
+    }
+}
+
+// A fixed arity is a number, a lambda with a rest parameter gives (min ...), and builtins report any
+pub fn builtin_procedure_arity(args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("procedure-arity", &args, 1)?;
+    match &args[0] {
+        Value::Function(callable) => Ok(match &**callable {
+            Callable::Builtin(_) | Callable::EvaluatorBuiltin(_) => Value::Symbol("any".to_string()),
+            Callable::Escape(_) => Value::Number(1.0),
+            Callable::Lambda { params, rest: None, .. } => Value::Number(params.len() as f64),
+            Callable::Lambda { params, rest: Some(_), .. } => Value::list(vec![
+                Value::Number(params.len() as f64),
+                Value::Symbol("...".to_string()),
+            ]),
+        }),
+        other => Err(EvalError::TypeError(format!(
+            "procedure-arity expects a procedure, got {}",
+            other.write_form()
+        ))),
+    }
+}
+
+pub fn builtin_describe(evaluator: &Evaluator, args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("describe", &args, 1)?;
+    let name = get_name_arg("describe", &args[0])?;
+    let value = evaluator.global_env.borrow().get(name)?;
+
+    let description = match &value {
+        Value::Function(callable) => match &**callable {
+            Callable::Builtin(_) | Callable::EvaluatorBuiltin(_) => format!("#<builtin: {}>", name),
+            Callable::Lambda { params, rest, body, .. } => {
+                let mut lines = vec![format!("{}: lambda {}", name, format_parameters(params, rest.as_deref()))];
+                let printer = PrettyPrinter::new(78);
+                for body_expr in body.iter() {
+                    let formatted = printer.format(&Value::from_expression(body_expr));
+                    lines.extend(formatted.lines().map(|line| format!("  {}", line)));
+                }
+                lines.join("\n")
+            }
+            Callable::Escape(_) => format!("{}: escape continuation", name),
+        },
+        _ => format!("{}: variable bound to {}", name, value.write_form()),
+    };
+    emit_output(&description);
+    emit_output("\n");
+    Ok(Value::Void)
+}
+
+pub fn builtin_trace(evaluator: &Evaluator, args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("trace", &args, 1)?;
+    let name = get_traced_name("trace", &args[0])?;
+    evaluator.trace(&name);
+    Ok(Value::Void)
+}
+
+pub fn builtin_untrace(evaluator: &Evaluator, args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("untrace", &args, 1)?;
+    let name = get_traced_name("untrace", &args[0])?;
+    evaluator.untrace(&name);
+    Ok(Value::Void)
+}
+
+// Control flow
+pub fn builtin_apply(evaluator: &Evaluator, args: Vec<Value>) -> Result<Value, EvalError> {
+    check_min_args("apply", &args, 2)?;
+    let mut args = args;
+    // (apply f a b rest) calls f with a, b and then every element of rest
+    let rest = args.pop().unwrap_or(Value::Nil);
+    let mut call_args = args.split_off(1);
+    call_args.extend(get_list_arg("apply", &rest)?);
+    let func = args.pop().unwrap_or(Value::Nil);
+    evaluator.apply(func, call_args)
+}
+
+pub fn builtin_call_with_escape_continuation(evaluator: &Evaluator, args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("call-with-escape-continuation", &args, 1)?;
+    let proc = args.into_iter().next().unwrap_or(Value::Nil);
+
+    let id = evaluator.new_escape_id();
+    let escape = Value::Function(Rc::new(Callable::Escape(id)));
+    match evaluator.apply(proc, vec![escape]) {
+        Err(EvalError::Escape(escape_id, value)) if escape_id == id => Ok(value),
+        result => result,
+    }
+}
+
+pub fn builtin_dynamic_wind(evaluator: &Evaluator, args: Vec<Value>) -> Result<Value, EvalError> {
+    check_num_args("dynamic-wind", &args, 3)?;
+    let mut args = args.into_iter();
+    let before = args.next().unwrap_or(Value::Nil);
+    let thunk = args.next().unwrap_or(Value::Nil);
+    let after = args.next().unwrap_or(Value::Nil);
+
+    evaluator.apply(before, Vec::new())?;
+    // after runs however the thunk exits: normally, with an error, or through an escape
+    let result = evaluator.apply(thunk, Vec::new());
+    evaluator.apply(after, Vec::new())?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{eval, numbers, string};
+
+    #[test]
+    fn format_interpolates_directives() {
+        assert_eq!(
+            eval("(format \"~a and ~s, ~d is ~x~%\" \"x\" \"y\" 255 255)"),
+            Ok(string("x and \"y\", 255 is ff\n"))
+        );
+        assert_eq!(eval("(format \"~~\")"), Ok(string("~")));
+        assert!(matches!(eval("(format \"~a\")"), Err(EvalError::WrongNumArgs(_))));
+        assert!(matches!(eval("(format \"~q\" 1)"), Err(EvalError::TypeError(_))));
+    }
+
+    #[test]
+    fn format_rejects_non_integers_for_integer_directives() {
+        assert_eq!(eval("(format \"~x ~d\" (- 0 255) 7)"), Ok(string("-ff 7")));
+        assert!(matches!(eval("(format \"~x\" 2.7)"), Err(EvalError::TypeError(_))));
+        assert!(matches!(eval("(format \"~d\" 2.5)"), Err(EvalError::TypeError(_))));
+    }
+
+    #[test]
+    fn random_seed_makes_random_repeatable() {
+        let result = eval(
+            "(random-seed! 42) (let a (random 1000))
+             (random-seed! 42) (let b (random 1000))
+             (= a b)",
+        );
+        assert_eq!(result, Ok(Value::Boolean(true)));
+        assert!(builtin_random(vec![Value::Number(0.0)]).is_err());
+        assert!(builtin_random(vec![Value::Number(f64::NAN)]).is_err());
+        assert!(builtin_random(vec![Value::Number(f64::INFINITY)]).is_err());
+    }
+
+    #[test]
+    fn string_split_and_join_round_trip() {
+        assert_eq!(
+            builtin_string_split(vec![string("a,b,c"), string(",")]),
+            Ok(Value::list(vec![string("a"), string("b"), string("c")]))
+        );
+        assert_eq!(eval("(string-join (string-split \"a b\" \" \") \"-\")"), Ok(string("a-b")));
+    }
+
+    #[test]
+    fn string_trimming() {
+        assert_eq!(builtin_string_trim(vec![string("  hi  ")]), Ok(string("hi  ")));
+        assert_eq!(builtin_string_trim_right(vec![string("  hi  ")]), Ok(string("  hi")));
+        assert_eq!(builtin_string_trim_both(vec![string("  hi  ")]), Ok(string("hi")));
+    }
+
+    #[test]
+    fn string_prefixes_and_suffixes() {
+        assert_eq!(builtin_string_prefix_p(vec![string("he"), string("hello")]), Ok(Value::Boolean(true)));
+        assert_eq!(builtin_string_suffix_p(vec![string("lo"), string("hello")]), Ok(Value::Boolean(true)));
+        assert_eq!(builtin_string_suffix_p(vec![string("he"), string("hello")]), Ok(Value::Boolean(false)));
+        assert_eq!(builtin_string_prefix_ci_p(vec![string("HE"), string("hello")]), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn string_replace_variants() {
+        assert_eq!(builtin_string_replace(vec![string("aXbX"), string("X"), string("-")]), Ok(string("a-b-")));
+        assert_eq!(builtin_string_replace_first(vec![string("aXbX"), string("X"), string("-")]), Ok(string("a-bX")));
+        assert_eq!(builtin_string_copy(vec![string("abc")]), Ok(string("abc")));
+    }
+
+    #[test]
+    fn bitwise_operations() {
+        assert_eq!(eval("(bitwise-and 12 10)"), Ok(Value::Number(8.0)));
+        assert_eq!(eval("(bitwise-or 12 10)"), Ok(Value::Number(14.0)));
+        assert_eq!(eval("(bitwise-xor 12 10)"), Ok(Value::Number(6.0)));
+        assert_eq!(eval("(bitwise-not 0)"), Ok(Value::Number(-1.0)));
+        assert_eq!(eval("(arithmetic-shift 1 4)"), Ok(Value::Number(16.0)));
+        assert_eq!(eval("(arithmetic-shift 16 (- 0 2))"), Ok(Value::Number(4.0)));
+        assert_eq!(eval("(bit-count 7)"), Ok(Value::Number(3.0)));
+    }
+
+    #[test]
+    fn arithmetic_shift_rejects_overflow() {
+        assert_eq!(eval("(arithmetic-shift 1 62)"), Ok(Value::Number(2f64.powi(62))));
+        assert_eq!(eval("(arithmetic-shift (- 0 1) 63)"), Ok(Value::Number(-(2f64.powi(63)))));
+        assert_eq!(eval("(arithmetic-shift 0 100)"), Ok(Value::Number(0.0)));
+        assert!(eval("(arithmetic-shift 1 63)").is_err());
+        assert!(eval("(arithmetic-shift 1 4294967296)").is_err());
+        assert!(eval("(arithmetic-shift 3 62)").is_err());
+        assert_eq!(eval("(arithmetic-shift (- 0 8) (- 0 100))"), Ok(Value::Number(-1.0)));
+        assert!(eval("(bitwise-and 10000000000000000000 1)").is_err());
+    }
+
+    #[test]
+    fn make_list_and_list_fill() {
+        assert_eq!(builtin_make_list(vec![Value::Number(2.0), Value::Number(7.0)]), Ok(numbers(&[7.0, 7.0])));
+        assert_eq!(eval("(let l (list 1 2)) (list-fill! l 0) l"), Ok(numbers(&[0.0, 0.0])));
+        assert!(builtin_make_list(vec![Value::Number(-1.0)]).is_err());
+    }
+
+    #[test]
+    fn make_list_refuses_huge_counts() {
+        assert!(matches!(eval("(make-list 1000000000000)"), Err(EvalError::TypeError(_))));
+        assert!(matches!(eval("(make-list (* 1000000000 1000000000 1000000000))"), Err(EvalError::TypeError(_))));
+    }
+
+    #[test]
+    fn output_can_be_captured_and_read_back() {
+        assert_eq!(eval("(with-output-to-string (lambda () (print 1 2) (print 3)))"), Ok(string("1 2\n3\n")));
+        assert_eq!(builtin_read_from_string(vec![string("(1 2)")]), Ok(numbers(&[1.0, 2.0])));
+    }
+
+    #[test]
+    fn write_quotes_strings_and_display_does_not() {
+        assert_eq!(eval("(with-output-to-string (lambda () (write \"a\")))"), Ok(string("\"a\"")));
+        assert_eq!(eval("(with-output-to-string (lambda () (display \"a\")))"), Ok(string("a")));
+    }
+
+    #[test]
+    fn written_strings_read_back_unchanged() {
+        let result = eval(
+            r#"(let s "a\\b \"q\"\n")
+               (list s (read-from-string (with-output-to-string (lambda () (write s)))))"#,
+        );
+        assert_eq!(result, Ok(Value::list(vec![string("a\\b \"q\"\n"), string("a\\b \"q\"\n")])));
+    }
+
+    #[test]
+    fn pretty_print_ends_with_a_newline() {
+        assert_eq!(eval("(with-output-to-string (lambda () (pp (list 1 2))))"), Ok(string("(1 2)\n")));
+    }
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("lisp-test-{}-{}", std::process::id(), name));
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn files_can_be_renamed_and_deleted() {
+        let file = temp_path("rename.txt");
+        let moved = temp_path("renamed.txt");
+        fs::write(&file, "contents").expect("test file should be writable");
+
+        assert_eq!(builtin_file_exists_p(vec![string(&file)]), Ok(Value::Boolean(true)));
+        assert_eq!(builtin_rename_file(vec![string(&file), string(&moved)]), Ok(Value::Void));
+        assert_eq!(builtin_file_exists_p(vec![string(&file)]), Ok(Value::Boolean(false)));
+        assert_eq!(builtin_delete_file(vec![string(&moved)]), Ok(Value::Void));
+        assert_eq!(builtin_file_exists_p(vec![string(&moved)]), Ok(Value::Boolean(false)));
+        assert!(matches!(builtin_delete_file(vec![string(&moved)]), Err(EvalError::IoError(_))));
+    }
+
+    #[test]
+    fn directories_can_be_made_and_listed() {
+        let dir = temp_path("listing");
+        assert_eq!(builtin_make_directory(vec![string(&dir)]), Ok(Value::Void));
+        fs::write(format!("{}/data.txt", dir), "contents").expect("test file should be writable");
+
+        assert_eq!(builtin_directory_list(vec![string(&dir)]), Ok(Value::list(vec![string("data.txt")])));
+        fs::remove_dir_all(&dir).expect("test directory should be removable");
+    }
+
+    #[test]
+    fn files_can_be_written_and_read_back() {
+        let file = temp_path("data.txt");
+        assert_eq!(builtin_write_file(vec![string(&file), string("contents")]), Ok(Value::Void));
+        assert_eq!(builtin_read_file(vec![string(&file)]), Ok(string("contents")));
+        fs::remove_file(&file).expect("test file should be removable");
+        assert!(matches!(builtin_read_file(vec![string(&file)]), Err(EvalError::IoError(_))));
+    }
+
+    #[test]
+    fn sleep_and_flush_output() {
+        assert_eq!(builtin_sleep(vec![Value::Number(1.0)]), Ok(Value::Void));
+        assert!(builtin_sleep(vec![Value::Number(-1.0)]).is_err());
+        assert_eq!(builtin_flush_output(vec![]), Ok(Value::Void));
+    }
+
+    #[test]
+    fn sleep_rejects_durations_too_long_to_represent() {
+        assert!(matches!(eval("(sleep (* 1000000000 1000000000 1000000000))"), Err(EvalError::TypeError(_))));
+    }
+
+    #[test]
+    fn integer_and_string_number_predicates() {
+        assert_eq!(builtin_is_integer(vec![Value::Number(3.0)]), Ok(Value::Boolean(true)));
+        assert_eq!(builtin_is_integer(vec![Value::Number(3.5)]), Ok(Value::Boolean(false)));
+        assert_eq!(builtin_is_string_number(vec![string("3.5")]), Ok(Value::Boolean(true)));
+        assert_eq!(builtin_is_string_number(vec![string("abc")]), Ok(Value::Boolean(false)));
+    }
+
+    #[test]
+    fn exactness_conversions() {
+        assert_eq!(builtin_exact_to_inexact(vec![Value::Number(2.0)]), Ok(Value::Number(2.0)));
+        assert_eq!(builtin_inexact_to_exact(vec![Value::Number(2.5)]), Ok(Value::Number(2.5)));
+        assert!(builtin_inexact_to_exact(vec![Value::Number(f64::INFINITY)]).is_err());
+    }
+
+    #[test]
+    fn boolean_equality() {
+        assert_eq!(eval("(boolean=? true true true)"), Ok(Value::Boolean(true)));
+        assert_eq!(eval("(boolean=? true false)"), Ok(Value::Boolean(false)));
+        assert!(eval("(boolean=? true 1)").is_err());
+    }
+
+    #[test]
+    fn case_insensitive_string_comparisons() {
+        assert_eq!(builtin_string_ci_eq(vec![string("Hello"), string("hELLO")]), Ok(Value::Boolean(true)));
+        assert_eq!(builtin_string_ci_lt(vec![string("apple"), string("BANANA")]), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn eof_object() {
+        assert_eq!(eval("(eof-object? (eof-object))"), Ok(Value::Boolean(true)));
+        assert_eq!(eval("(eof-object? 1)"), Ok(Value::Boolean(false)));
+        assert_eq!(builtin_read_from_string(vec![string("")]), Ok(Value::Eof));
+    }
+
+    #[test]
+    fn describe_shows_a_lambda_and_its_body() {
+        assert_eq!(
+            eval("(let sq (lambda (x) (* x x))) (with-output-to-string (lambda () (describe \"sq\")))"),
+            Ok(string("sq: lambda (x)\n  (* x x)\n"))
+        );
+    }
+
+    #[test]
+    fn error_objects_carry_message_and_irritants() {
+        let error = Value::ErrorObject {
+            message: "bad thing:".to_string(),
+            irritants: vec![Value::Number(1.0), Value::Number(2.0)],
+        };
+        assert_eq!(eval("(error \"bad thing:\" 1 2)"), Err(EvalError::Raised(error.clone())));
+        assert_eq!(builtin_is_error_object(vec![error.clone()]), Ok(Value::Boolean(true)));
+        assert_eq!(builtin_error_message(vec![error.clone()]), Ok(string("bad thing:")));
+        assert_eq!(builtin_error_irritants(vec![error]), Ok(numbers(&[1.0, 2.0])));
+    }
+
+    #[test]
+    fn pairs_are_mutable() {
+        assert_eq!(eval("(let p (cons 1 2)) (set-car! p 3) (set-cdr! p (list 4)) p"), Ok(numbers(&[3.0, 4.0])));
+        assert!(builtin_car(vec![Value::Nil]).is_err());
+    }
+
+    #[test]
+    fn number_formatting() {
+        assert_eq!(builtin_number_format(vec![Value::Number(1.23456), Value::Number(2.0)]), Ok(string("1.23")));
+        assert_eq!(builtin_number_to_string_e(vec![Value::Number(1234.5), Value::Number(2.0)]), Ok(string("1.23e3")));
+    }
+
+    #[test]
+    fn number_formatting_rejects_huge_precision() {
+        assert!(matches!(eval("(number-format 1 65536)"), Err(EvalError::TypeError(_))));
+        assert!(matches!(eval("(number->string/e 1 100000)"), Err(EvalError::TypeError(_))));
+        assert!(matches!(eval("(number-format 1 (- 0 1))"), Err(EvalError::TypeError(_))));
+    }
+
+    #[test]
+    fn symbol_comparisons() {
+        assert_eq!(eval("(symbol=? (read-from-string \"a\") (read-from-string \"a\"))"), Ok(Value::Boolean(true)));
+        assert_eq!(eval("(symbol<? (read-from-string \"a\") (read-from-string \"b\"))"), Ok(Value::Boolean(true)));
+        assert!(eval("(symbol=? (read-from-string \"a\") \"a\")").is_err());
+    }
+
+    #[test]
+    fn print_variants_write_to_captured_output() {
+        assert_eq!(eval("(with-output-to-string (lambda () (print 1 \"a\") (print* 2)))"), Ok(string("1 a\n2")));
+    }
+
+    #[test]
+    fn counting() {
+        assert_eq!(eval("(count (lambda (x) (> x 1)) (list 1 2 3))"), Ok(Value::Number(2.0)));
+        assert_eq!(eval("(count-if-not (lambda (x) (> x 1)) (list 1 2 3))"), Ok(Value::Number(1.0)));
+    }
+
+    #[test]
+    fn reducing() {
+        assert_eq!(eval("(reduce + 0 (list 1 2 3))"), Ok(Value::Number(6.0)));
+        assert_eq!(eval("(reduce + 0 (list))"), Ok(Value::Number(0.0)));
+        assert_eq!(eval("(reduce-right - 0 (list 1 2 3))"), Ok(Value::Number(2.0)));
+    }
+
+    #[test]
+    fn zip_and_unzip() {
+        assert_eq!(
+            eval("(zip (list 1 2) (list 3 4 5))"),
+            Ok(Value::list(vec![numbers(&[1.0, 3.0]), numbers(&[2.0, 4.0])]))
+        );
+        assert_eq!(eval("(unzip1 (list (list 1 2) (list 3 4)))"), Ok(numbers(&[1.0, 3.0])));
+    }
+
+    #[test]
+    fn last_and_but_last() {
+        assert_eq!(eval("(last (list 1 2 3))"), Ok(Value::Number(3.0)));
+        assert_eq!(eval("(but-last (list 1 2 3))"), Ok(numbers(&[1.0, 2.0])));
+        assert!(eval("(last (list))").is_err());
+    }
+
+    #[test]
+    fn concatenate_flattens_one_level() {
+        assert_eq!(eval("(concatenate (list (list 1) (list) (list 2 3)))"), Ok(numbers(&[1.0, 2.0, 3.0])));
+    }
+
+    #[test]
+    fn number_to_string_with_a_radix() {
+        assert_eq!(builtin_number_to_string(vec![Value::Number(255.0), Value::Number(16.0)]), Ok(string("ff")));
+        assert_eq!(builtin_number_to_string(vec![Value::Number(-5.0), Value::Number(2.0)]), Ok(string("-101")));
+        assert_eq!(builtin_number_to_string(vec![Value::Number(2.5)]), Ok(string("2.5")));
+    }
+
+    #[test]
+    fn exception_handlers_see_raised_values() {
+        let result = eval("(with-exception-handler (lambda (e) (* e 2)) (lambda () (+ 1 (raise-continuable 20))))");
+        assert_eq!(result, Ok(Value::Number(41.0)));
+        let result = eval("(call/ec (lambda (k) (with-exception-handler (lambda (e) (k e)) (lambda () (raise 5)))))");
+        assert_eq!(result, Ok(Value::Number(5.0)));
+    }
+
+    #[test]
+    fn raise_calls_the_handler_before_unwinding() {
+        let result = eval(
+            "(let log (list 0))
+             (let note (lambda (x) (set-cdr! log (cons x (cdr log)))))
+             (call/ec (lambda (k)
+               (with-exception-handler
+                 (lambda (e) (note e) (k 0))
+                 (lambda () (dynamic-wind (lambda () (note 1)) (lambda () (raise 2)) (lambda () (note 3)))))))
+             (reverse (cdr log))",
+        );
+        assert_eq!(result, Ok(numbers(&[1.0, 2.0, 3.0])));
+    }
+
+    #[test]
+    fn returning_handlers_pass_the_raise_outwards() {
+        let result = eval(
+            "(let log (list 0))
+             (let note (lambda (x) (set-cdr! log (cons x (cdr log)))))
+             (ignore-errors
+               (with-exception-handler (lambda (e) (note (+ e 1)))
+                 (lambda () (with-exception-handler (lambda (e) (note e)) (lambda () (raise 5))))))
+             (reverse (cdr log))",
+        );
+        assert_eq!(result, Ok(numbers(&[5.0, 6.0])));
+        assert_eq!(eval("(with-exception-handler (lambda (e) 0) (lambda () (raise 5)))"), Err(EvalError::Raised(Value::Number(5.0))));
+    }
+
+    #[test]
+    fn ignore_errors_hides_raises_from_outer_handlers() {
+        let result = eval(
+            "(let seen (list false))
+             (with-exception-handler (lambda (e) (set-car! seen true))
+               (lambda () (ignore-errors (raise 1))))
+             (car seen)",
+        );
+        assert_eq!(result, Ok(Value::Boolean(false)));
+    }
+
+    #[test]
+    fn interpreter_errors_reach_handlers_as_error_objects() {
+        let result = eval("(call/ec (lambda (k) (with-exception-handler (lambda (e) (k (error-object? e))) (lambda () (car 1)))))");
+        assert_eq!(result, Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn bytevectors() {
+        assert_eq!(eval("(let b (make-bytevector 2 9)) (bytevector-u8-set! b 1 3) b"),
+            Ok(Value::Bytevector(Rc::new(RefCell::new(vec![9, 3])))));
+        assert_eq!(eval("(bytevector-length (bytevector 1 2 3))"), Ok(Value::Number(3.0)));
+        assert!(eval("(bytevector 256)").is_err());
+        assert!(eval("(bytevector-u8-ref (bytevector 1) 1)").is_err());
+    }
+
+    #[test]
+    fn make_bytevector_refuses_huge_sizes() {
+        assert!(matches!(eval("(make-bytevector 1000000000000)"), Err(EvalError::TypeError(_))));
+        assert!(matches!(eval("(make-bytevector (- 0 1))"), Err(EvalError::TypeError(_))));
+    }
+
+    #[test]
+    fn strings_convert_to_and_from_utf8() {
+        assert_eq!(eval("(utf8->string (string->utf8 \"héllo\"))"), Ok(string("héllo")));
+        assert_eq!(eval("(utf8->string (string->utf8 \"hello\" 1 3))"), Ok(string("el")));
+        assert!(eval("(utf8->string (bytevector 255))").is_err());
+    }
+
+    #[test]
+    fn max_and_min() {
+        assert_eq!(eval("(max 1 5 3)"), Ok(Value::Number(5.0)));
+        assert_eq!(eval("(min 4 2 8)"), Ok(Value::Number(2.0)));
+        assert!(eval("(max)").is_err());
+    }
+
+    #[test]
+    fn exact_integer_predicate() {
+        assert_eq!(builtin_is_exact_integer(vec![Value::Number(3.0)]), Ok(Value::Boolean(true)));
+        assert_eq!(builtin_is_exact_integer(vec![Value::Number(3.5)]), Ok(Value::Boolean(false)));
+        assert_eq!(builtin_is_exact_integer(vec![string("3")]), Ok(Value::Boolean(false)));
+    }
+
+    #[test]
+    fn list_shape_predicates() {
+        let circular = "(let c (list 1 2 3)) (set-cdr! (cdr (cdr c)) c)";
+        assert_eq!(eval("(proper-list? (list 1 2 3))"), Ok(Value::Boolean(true)));
+        assert_eq!(eval("(dotted-list? (cons 1 2))"), Ok(Value::Boolean(true)));
+        assert_eq!(eval(&format!("{} (circular-list? c)", circular)), Ok(Value::Boolean(true)));
+        assert_eq!(eval(&format!("{} (proper-list? c)", circular)), Ok(Value::Boolean(false)));
+    }
+
+    #[test]
+    fn circular_lists_can_be_compared_and_printed() {
+        let circular = "(let c (list 1 2 3)) (set-cdr! (cdr (cdr c)) c)";
+        let printed = |expr: &str| eval(&format!("{} (with-output-to-string (lambda () {}))", circular, expr));
+        assert_eq!(eval(&format!("{} (= c c)", circular)), Ok(Value::Boolean(true)));
+        assert_eq!(printed("(print c)"), Ok(string("#0=(1 2 3 . #0#)\n")));
+        assert_eq!(printed("(pp c)"), Ok(string("#0=(1 2 3 . #0#)\n")));
+        assert_eq!(printed("(describe \"c\")"), Ok(string("c: variable bound to #0=(1 2 3 . #0#)\n")));
+
+        // Too wide for one line, but breaking it up would recurse into itself through the car
+        let wide = eval("(let c (iota 40)) (set-car! c c) (with-output-to-string (lambda () (pp c)))");
+        assert!(matches!(wide, Ok(Value::String(s)) if s.starts_with("#0=(#0# 1 2 3") && !s.trim_end().contains('\n')));
+    }
+
+    #[test]
+    fn deep_copy_shares_nothing_mutable() {
+        let result = eval(
+            "(let x (list (list 1 2) (bytevector 1)))
+             (let y (deep-copy x))
+             (set-car! (car y) 99)
+             (bytevector-u8-set! (car (cdr y)) 0 7)
+             x",
+        );
+        let original = Value::list(vec![numbers(&[1.0, 2.0]), Value::Bytevector(Rc::new(RefCell::new(vec![1])))]);
+        assert_eq!(result, Ok(original));
+    }
+
+    fn pair(value: &Value) -> Rc<RefCell<(Value, Value)>> {
+        match value {
+            Value::Pair(pair) => pair.clone(),
+            other => panic!("expected a pair, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn deep_copy_preserves_cycles_and_sharing() {
+        // A cycle through the car: the copy's car is the copy itself, not the original
+        let copy = pair(&eval("(let c (list 1)) (set-car! c c) (deep-copy c)").unwrap());
+        assert!(Rc::ptr_eq(&pair(&copy.borrow().0), &copy));
+
+        // A circular list nested inside a proper one
+        let outer = pair(&eval("(let c (list 1 2)) (set-cdr! (cdr c) c) (deep-copy (list 0 c))").unwrap());
+        let inner = pair(&pair(&outer.borrow().1).borrow().0);
+        let second = pair(&inner.borrow().1);
+        assert_eq!(second.borrow().0, Value::Number(2.0));
+        assert!(Rc::ptr_eq(&pair(&second.borrow().1), &inner));
+
+        // A circular top level
+        let copy = pair(&eval("(let c (list 1 2 3)) (set-cdr! (cdr (cdr c)) c) (deep-copy c)").unwrap());
+        let third = pair(&pair(&copy.borrow().1).borrow().1);
+        assert!(Rc::ptr_eq(&pair(&third.borrow().1), &copy));
+
+        // Shared structure stays shared in the copy, but not with the original
+        let both = eval("(let s (list 1)) (let x (list s s)) (list x (deep-copy x))").unwrap();
+        let original = pair(&pair(&both).borrow().0);
+        let copy = pair(&pair(&pair(&both).borrow().1).borrow().0);
+        let first = pair(&copy.borrow().0);
+        assert!(Rc::ptr_eq(&first, &pair(&pair(&copy.borrow().1).borrow().0)));
+        assert!(!Rc::ptr_eq(&first, &pair(&original.borrow().0)));
+    }
+
+    #[test]
+    fn procedure_arity_by_kind() {
+        assert_eq!(eval("(procedure-arity (lambda (x y) x))"), Ok(Value::Number(2.0)));
+        assert_eq!(
+            eval("(procedure-arity (lambda (x . r) x))"),
+            Ok(Value::list(vec![Value::Number(1.0), Value::Symbol("...".to_string())]))
+        );
+        assert_eq!(eval("(procedure-arity car)"), Ok(Value::Symbol("any".to_string())));
+    }
+
+    #[test]
+    fn string_builder_accumulates() {
+        let result = eval(
+            "(let sb (make-string-builder))
+             (string-builder-append! sb \"hello\")
+             (string-builder-append! sb \" \" \"world\")
+             (string-builder->string sb)",
+        );
+        assert_eq!(result, Ok(string("hello world")));
+    }
+
+    #[test]
+    fn infinities_and_nan() {
+        assert_eq!(eval("(list (number? +inf.0) (infinite? -inf.0) (nan? +nan.0) (finite? 1.5) (finite? +inf.0))"),
+            Ok(Value::list(vec![
+                Value::Boolean(true),
+                Value::Boolean(true),
+                Value::Boolean(true),
+                Value::Boolean(true),
+                Value::Boolean(false),
+            ])));
+        assert_eq!(eval("(number->string -inf.0)"), Ok(string("-inf.0")));
+        assert!(builtin_is_nan(vec![string("x")]).is_err());
+    }
+
+    #[test]
+    fn string_format_number_directives() {
+        assert_eq!(builtin_string_format_number(vec![Value::Number(1.23456), string("~.2f")]), Ok(string("1.23")));
+        assert_eq!(builtin_string_format_number(vec![Value::Number(42.0), string("~5d")]), Ok(string("   42")));
+        assert_eq!(builtin_string_format_number(vec![Value::Number(1234.5), string("~e")]), Ok(string("1.2345e3")));
+        assert!(builtin_string_format_number(vec![Value::Number(1.0), string("~x")]).is_err());
+    }
+
+    #[test]
+    fn string_format_number_rejects_bad_width_and_precision() {
+        for fmt in ["~99999999999999d", "~65536f", "~,65536f", "~.100000e", "~.2d", "~5,0d"] {
+            let result = builtin_string_format_number(vec![Value::Number(1.0), string(fmt)]);
+            assert!(matches!(result, Err(EvalError::TypeError(_))), "{} should be rejected", fmt);
+        }
+        assert_eq!(builtin_string_format_number(vec![Value::Number(7.0), string("~3,1f")]), Ok(string("7.0")));
+    }
+}
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use std::rc::Rc;
 use std::cell::RefCell;
 
-use crate::evaluator::{Value, Callable, EvalError};
+use crate::evaluator::{Value, Callable, EvalError, BuiltinFn, EvaluatorBuiltinFn};
 use crate::evaluator::builtins;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -12,6 +12,12 @@ pub struct Environment {
     parent: Option<Rc<RefCell<Environment>>>,
 }
 
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Environment {
     pub fn new() -> Self {
         let mut env = Environment {
@@ -19,24 +25,159 @@ impl Environment {
             parent: None,
         };
 
-        let builtins_to_register: &[(&str, fn(Vec<Value>) -> Result<Value, EvalError>)] = &[
+        let builtins_to_register: &[(&str, BuiltinFn)] = &[
             ("+", builtins::builtin_add),
             ("-", builtins::builtin_sub),
             ("*", builtins::builtin_mul),
             ("/", builtins::builtin_div),
+            ("max", builtins::builtin_max),
+            ("min", builtins::builtin_min),
             ("=", builtins::builtin_eq),
             ("!=", builtins::builtin_ne),
             (">", builtins::builtin_gt),
             ("<", builtins::builtin_lt),
             (">=", builtins::builtin_ge),
             ("<=", builtins::builtin_le),
+            ("boolean=?", builtins::builtin_boolean_eq),
+            ("symbol=?", builtins::builtin_symbol_eq),
+            ("symbol<?", builtins::builtin_symbol_lt),
+            ("symbol>?", builtins::builtin_symbol_gt),
+            ("symbol<=?", builtins::builtin_symbol_le),
+            ("symbol>=?", builtins::builtin_symbol_ge),
+            ("number?", builtins::builtin_is_number),
+            ("finite?", builtins::builtin_is_finite),
+            ("infinite?", builtins::builtin_is_infinite),
+            ("nan?", builtins::builtin_is_nan),
+            ("integer?", builtins::builtin_is_integer),
+            ("exact-integer?", builtins::builtin_is_exact_integer),
+            ("string-number?", builtins::builtin_is_string_number),
+            ("exact->inexact", builtins::builtin_exact_to_inexact),
+            ("inexact->exact", builtins::builtin_inexact_to_exact),
             ("print", builtins::builtin_print),
+            ("print*", builtins::builtin_print_star),
+            ("eprint", builtins::builtin_eprint),
+            ("eprintln", builtins::builtin_eprintln),
+            ("display", builtins::builtin_display),
+            ("write", builtins::builtin_write),
+            ("pp", builtins::builtin_pp),
+            ("pretty-print", builtins::builtin_pp),
+            ("format", builtins::builtin_format),
+            ("number-format", builtins::builtin_number_format),
+            ("number->string", builtins::builtin_number_to_string),
+            ("number->string/e", builtins::builtin_number_to_string_e),
+            ("string-format-number", builtins::builtin_string_format_number),
+            ("random", builtins::builtin_random),
+            ("random-seed!", builtins::builtin_random_seed),
+            ("current-time", builtins::builtin_current_time),
+            ("sleep", builtins::builtin_sleep),
+            ("flush-output", builtins::builtin_flush_output),
+            ("list", builtins::builtin_list),
+            ("null?", builtins::builtin_is_null),
+            ("pair?", builtins::builtin_is_pair),
+            ("list?", builtins::builtin_proper_list_p),
+            ("proper-list?", builtins::builtin_proper_list_p),
+            ("dotted-list?", builtins::builtin_dotted_list_p),
+            ("circular-list?", builtins::builtin_circular_list_p),
+            ("cons", builtins::builtin_cons),
+            ("car", builtins::builtin_car),
+            ("cdr", builtins::builtin_cdr),
+            ("set-car!", builtins::builtin_set_car),
+            ("set-cdr!", builtins::builtin_set_cdr),
+            ("zip", builtins::builtin_zip),
+            ("unzip1", builtins::builtin_unzip1),
+            ("last", builtins::builtin_last),
+            ("but-last", builtins::builtin_but_last),
+            ("concatenate", builtins::builtin_concatenate),
+            ("check-list-lengths", builtins::builtin_check_list_lengths),
+            ("deep-copy", builtins::builtin_deep_copy),
+            ("list-copy/deep", builtins::builtin_deep_copy),
+            ("make-list", builtins::builtin_make_list),
+            ("list-fill!", builtins::builtin_list_fill),
+            ("read-from-string", builtins::builtin_read_from_string),
+            ("environment-ref", builtins::builtin_environment_ref),
+            ("eof-object", builtins::builtin_eof_object),
+            ("eof-object?", builtins::builtin_is_eof),
+            ("file-exists?", builtins::builtin_file_exists_p),
+            ("delete-file", builtins::builtin_delete_file),
+            ("rename-file", builtins::builtin_rename_file),
+            ("directory-list", builtins::builtin_directory_list),
+            ("make-directory", builtins::builtin_make_directory),
+            ("read-file", builtins::builtin_read_file),
+            ("write-file", builtins::builtin_write_file),
+            ("string-split", builtins::builtin_string_split),
+            ("string-join", builtins::builtin_string_join),
+            ("string-trim", builtins::builtin_string_trim),
+            ("string-trim-right", builtins::builtin_string_trim_right),
+            ("string-trim-both", builtins::builtin_string_trim_both),
+            ("string-prefix?", builtins::builtin_string_prefix_p),
+            ("string-suffix?", builtins::builtin_string_suffix_p),
+            ("string-prefix-ci?", builtins::builtin_string_prefix_ci_p),
+            ("string-suffix-ci?", builtins::builtin_string_suffix_ci_p),
+            ("string-ci=?", builtins::builtin_string_ci_eq),
+            ("string-ci<?", builtins::builtin_string_ci_lt),
+            ("string-ci>?", builtins::builtin_string_ci_gt),
+            ("string-ci<=?", builtins::builtin_string_ci_le),
+            ("string-ci>=?", builtins::builtin_string_ci_ge),
+            ("string-replace", builtins::builtin_string_replace),
+            ("string-replace-first", builtins::builtin_string_replace_first),
+            ("string-copy", builtins::builtin_string_copy),
+            ("make-string-builder", builtins::builtin_make_string_builder),
+            ("string-builder-append!", builtins::builtin_string_builder_append),
+            ("string-builder->string", builtins::builtin_string_builder_to_string),
+            ("bitwise-and", builtins::builtin_bitwise_and),
+            ("bitwise-or", builtins::builtin_bitwise_or),
+            ("bitwise-xor", builtins::builtin_bitwise_xor),
+            ("bitwise-not", builtins::builtin_bitwise_not),
+            ("arithmetic-shift", builtins::builtin_arithmetic_shift),
+            ("bit-count", builtins::builtin_bit_count),
+            ("make-bytevector", builtins::builtin_make_bytevector),
+            ("bytevector", builtins::builtin_bytevector),
+            ("bytevector?", builtins::builtin_is_bytevector),
+            ("bytevector-length", builtins::builtin_bytevector_length),
+            ("bytevector-u8-ref", builtins::builtin_bytevector_u8_ref),
+            ("bytevector-u8-set!", builtins::builtin_bytevector_u8_set),
+            ("string->utf8", builtins::builtin_string_to_utf8),
+            ("utf8->string", builtins::builtin_utf8_to_string),
+            ("error-object?", builtins::builtin_is_error_object),
+            ("error-message", builtins::builtin_error_message),
+            ("error-irritants", builtins::builtin_error_irritants),
+            ("condition?", builtins::builtin_is_condition),
+            ("condition/ref", builtins::builtin_condition_ref),
+            ("condition/message", builtins::builtin_condition_message),
+            ("condition-type/name", builtins::builtin_condition_type_name),
+            ("procedure-arity", builtins::builtin_procedure_arity),
         ];
 
         for (name, func) in builtins_to_register {
             env.define(name.to_string(), Value::Function(Rc::new(Callable::Builtin(*func))));
         }
 
+        let evaluator_builtins_to_register: &[(&str, EvaluatorBuiltinFn)] = &[
+            ("count", builtins::builtin_count),
+            ("count-if-not", builtins::builtin_count_if_not),
+            ("reduce", builtins::builtin_reduce),
+            ("reduce-right", builtins::builtin_reduce_right),
+            ("eval", builtins::builtin_eval),
+            ("interaction-environment", builtins::builtin_interaction_environment),
+            ("error", builtins::builtin_error),
+            ("raise", builtins::builtin_raise),
+            ("raise-continuable", builtins::builtin_raise_continuable),
+            ("with-exception-handler", builtins::builtin_with_exception_handler),
+            ("with-output-to-string", builtins::builtin_with_output_to_string),
+            ("apply", builtins::builtin_apply),
+            ("call-with-escape-continuation", builtins::builtin_call_with_escape_continuation),
+            ("call/ec", builtins::builtin_call_with_escape_continuation),
+            ("dynamic-wind", builtins::builtin_dynamic_wind),
+            ("make-condition", builtins::builtin_make_condition),
+            ("describe", builtins::builtin_describe),
+            ("trace", builtins::builtin_trace),
+            ("untrace", builtins::builtin_untrace),
+        ];
+
+        for (name, func) in evaluator_builtins_to_register {
+            env.define(name.to_string(), Value::Function(Rc::new(Callable::EvaluatorBuiltin(*func))));
+        }
+
         env
     }
 
@@ -57,13 +198,39 @@ impl Environment {
         }
     }
 
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.store.keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    pub fn to_alist(&self) -> Vec<(String, Value)> {
+        let mut bindings: Vec<(String, Value)> = self
+            .store
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+
+    pub fn all_keys(&self) -> Vec<String> {
+        let mut keys = self.keys();
+        if let Some(parent_env) = &self.parent {
+            keys.extend(parent_env.borrow().all_keys());
+        }
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
     pub fn define(&mut self, name: String, value: Value) {
         self.store.insert(name, value);
     }
 
     pub fn set(&mut self, name: String, value: Value) -> Result<(), EvalError> {
-        if self.store.contains_key(&name) {
-            self.store.insert(name, value);
+        if let Some(slot) = self.store.get_mut(&name) {
+            *slot = value;
             Ok(())
         } else if let Some(parent_env) = &self.parent {
             parent_env.borrow_mut().set(name, value)
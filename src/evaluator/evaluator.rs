@@ -1,9 +1,15 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::fmt;
 use std::rc::Rc;
+use std::thread;
+use std::time::Instant;
 
 use crate::ast::Expression;
-use crate::evaluator::{Environment, Callable, Value};
+use crate::evaluator::{builtins, Environment, Callable, Value, BuiltinFn};
+use crate::parser::Parser;
+use crate::tokenizer::Tokenizer;
 
 #[derive(Debug, PartialEq)]
 pub enum EvalError {
@@ -13,6 +19,12 @@ pub enum EvalError {
     NotCallable(Value),        // Attempt to call a non-function value
     SpecialFormError(String),  // General error for malformed special forms
     DivisionByZero,            // Attempt to divide by zero
+    ReadError(String),         // Source text passed to the reader could not be parsed
+    IoError(String),           // A file system or stream operation failed
+    AssertionFailed(String),   // An (assert ...) condition evaluated to false
+    Escape(usize, Value),      // An escape continuation was invoked; unwinds to its call/ec
+    StackOverflow,             // Evaluation nested past the evaluator's depth or stack limit
+    Raised(Value),             // A value raised by Lisp code, e.g. the error object from (error ...)
 }
 
 impl fmt::Display for EvalError {
@@ -36,6 +48,34 @@ impl fmt::Display for EvalError {
             EvalError::DivisionByZero => {
                 write!(f, "Division by zero")
             }
+            EvalError::ReadError(msg) => {
+                write!(f, "Read error: {}", msg)
+            }
+            EvalError::IoError(msg) => {
+                write!(f, "I/O error: {}", msg)
+            }
+            EvalError::AssertionFailed(msg) => {
+                write!(f, "Assertion failed: {}", msg)
+            }
+            EvalError::Escape(_, _) => {
+                write!(f, "Escape continuation invoked after its call/ec returned")
+            }
+            EvalError::StackOverflow => {
+                write!(f, "Stack overflow: maximum recursion depth exceeded")
+            }
+            EvalError::Raised(Value::ErrorObject { message, irritants }) => {
+                write!(f, "{}", message)?;
+                for irritant in irritants {
+                    write!(f, " {}", irritant.write_form())?;
+                }
+                Ok(())
+            }
+            EvalError::Raised(Value::Condition { type_name, message, .. }) => {
+                write!(f, "{}: {}", type_name, message)
+            }
+            EvalError::Raised(value) => {
+                write!(f, "Uncaught raise: {}", value.write_form())
+            }
         }
     }
 }
@@ -43,35 +83,161 @@ impl fmt::Display for EvalError {
 #[derive(Debug)]
 pub struct Evaluator {
     pub global_env: Rc<RefCell<Environment>>,
+    next_escape_id: Cell<usize>,
+    max_depth: usize,
+    current_depth: Cell<usize>,
+    stack_limit: usize,       // Bytes of stack nested evaluations may use
+    stack_base: Cell<usize>,  // Stack address of the outermost evaluate call
+    traced: RefCell<HashSet<String>>, // Names of lambdas whose calls are reported by trace
+    condition_types: RefCell<HashMap<String, Vec<String>>>, // Field names of each condition type
+}
+
+const DEFAULT_MAX_DEPTH: usize = 10_000;
+
+// Stack left unused below the limit for builtins, printing and the embedder's own frames
+const STACK_RESERVE: usize = 512 * 1024;
+
+// std cannot report the current thread's stack size, so assume the platform default:
+// the main thread's usual 8 MiB (1 MiB on Windows), or Rust's 2 MiB for spawned threads
+// unless RUST_MIN_STACK says otherwise
+fn default_stack_size() -> usize {
+    if thread::current().name() == Some("main") {
+        return if cfg!(windows) { 1024 * 1024 } else { 8 * 1024 * 1024 };
+    }
+    env::var("RUST_MIN_STACK")
+        .ok()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(2 * 1024 * 1024)
+}
+
+// An address in the caller's stack frame; the distance between two of them is the stack used in between
+#[inline(never)]
+fn stack_address() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+// Library procedures written in Lisp on top of the builtins, loaded into every new evaluator
+const STDLIB_SOURCE: &str = include_str!("../stdlib.lisp");
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Evaluator {
     pub fn new() -> Self {
-        Evaluator {
+        let evaluator = Evaluator {
             global_env: Rc::new(RefCell::new(Environment::new())),
+            next_escape_id: Cell::new(0),
+            max_depth: DEFAULT_MAX_DEPTH,
+            current_depth: Cell::new(0),
+            stack_limit: default_stack_size().saturating_sub(STACK_RESERVE),
+            stack_base: Cell::new(0),
+            traced: RefCell::new(HashSet::new()),
+            condition_types: RefCell::new(HashMap::new()),
+        };
+        evaluator.load_stdlib();
+        evaluator
+    }
+
+    fn load_stdlib(&self) {
+        let program = Parser::new(Tokenizer::new(STDLIB_SOURCE))
+            .parse()
+            .expect("stdlib.lisp should parse");
+        self.eval_program(&program)
+            .expect("stdlib.lisp should evaluate");
+    }
+
+    pub fn with_max_depth(max_depth: usize) -> Self {
+        Evaluator {
+            max_depth,
+            ..Self::new()
+        }
+    }
+
+    // For an evaluator that will run on a thread with stack_size bytes of stack
+    pub fn with_stack_size(stack_size: usize) -> Self {
+        Evaluator {
+            stack_limit: stack_size.saturating_sub(STACK_RESERVE),
+            ..Self::new()
         }
     }
 
+    pub fn new_escape_id(&self) -> usize {
+        let id = self.next_escape_id.get();
+        self.next_escape_id.set(id + 1);
+        id
+    }
+
+    pub fn trace(&self, name: &str) {
+        self.traced.borrow_mut().insert(name.to_string());
+    }
+
+    pub fn untrace(&self, name: &str) {
+        self.traced.borrow_mut().remove(name);
+    }
+
+    pub fn condition_type_fields(&self, type_name: &str) -> Option<Vec<String>> {
+        self.condition_types.borrow().get(type_name).cloned()
+    }
+
+    pub fn register_builtin(&self, name: &str, f: BuiltinFn) {
+        self.register_value(name, Value::Function(Rc::new(Callable::Builtin(f))));
+    }
+
+    pub fn register_value(&self, name: &str, value: Value) {
+        self.global_env.borrow_mut().define(name.to_string(), value);
+    }
+
     pub fn evaluate(&self, expr: &Expression, env: Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
-        match expr {
-            Expression::Number(n) => Ok(Value::Number(*n)),
-            Expression::String(s) => Ok(Value::String(s.clone())),
-            Expression::Boolean(b) => Ok(Value::Boolean(*b)),
+        // Nested evaluations are counted and their stack use measured, so runaway recursion
+        // fails cleanly instead of overflowing the Rust stack
+        let depth = self.current_depth.get();
+        let here = stack_address();
+        if depth == 0 {
+            self.stack_base.set(here);
+        }
+        if depth >= self.max_depth || self.stack_base.get().abs_diff(here) > self.stack_limit {
+            return Err(EvalError::StackOverflow);
+        }
+        self.current_depth.set(depth + 1);
+        let result = self.eval_expression(expr, env);
+        self.current_depth.set(depth);
+        result
+    }
 
-            Expression::Identifier(name) => {
-                env.borrow().get(name)
-            }
+    fn eval_expression(&self, expr: &Expression, env: Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+        // Expressions in tail position replace `expr` and `env` and go around the loop
+        // instead of recursing, so tail calls run in constant stack space
+        let mut expr = expr;
+        let mut env = env;
+        // Keeps the body of a tail-called lambda alive while `expr` points into it
+        let mut tail_body: Rc<[Expression]>;
 
-            Expression::List(elements) => {
-                if elements.is_empty() {
-                    return Ok(Value::Nil);
+        loop {
+            return match expr {
+                Expression::Number(n) => Ok(Value::Number(*n)),
+                Expression::String(s) => Ok(Value::String(s.clone())),
+                Expression::Boolean(b) => Ok(Value::Boolean(*b)),
+
+                Expression::Identifier(name) => {
+                    env.borrow().get(name)
                 }
 
-                let head = &elements[0];
+                Expression::List(elements) => {
+                    if elements.is_empty() {
+                        return Ok(Value::Nil);
+                    }
 
-                if let Expression::Identifier(op) = head {
-                    match op.as_str() {
-                        "if" => {
+                    let special_form = match &elements[0] {
+                        Expression::Identifier(op) => Some(op.as_str()),
+                        _ => None,
+                    };
+
+                    match special_form {
+                        Some("if") => {
                             if elements.len() < 3 || elements.len() > 4 {
                                 return Err(EvalError::WrongNumArgs(
                                     "if expects 2 or 3 arguments (condition then-expr [else-expr])"
@@ -79,82 +245,447 @@ impl Evaluator {
                                 ));
                             }
                             let condition = self.evaluate(&elements[1], env.clone())?;
-                            if let Value::Boolean(true) = condition {
-                                self.evaluate(&elements[2], env)
+                            if is_truthy(&condition) {
+                                expr = &elements[2];
+                            } else if elements.len() == 4 {
+                                expr = &elements[3];
                             } else {
-                                if elements.len() == 4 {
-                                    self.evaluate(&elements[3], env)
-                                } else {
-                                    Ok(Value::Nil)
-                                }
+                                return Ok(Value::Void);
                             }
+                            continue;
                         }
-                        "let" => {
-                            if elements.len() != 3 {
+                        Some("let") if matches!(elements.get(1), Some(Expression::List(_))) => {
+                            if elements.len() < 3 {
                                 return Err(EvalError::WrongNumArgs(
-                                    "let expects 2 arguments (variable value)".to_string(),
+                                    "let expects ((var value) ...) body".to_string(),
                                 ));
                             }
-                            let var_name_expr = &elements[1];
-                            let value_expr = &elements[2];
+                            let bindings = parse_bindings("let", &elements[1])?;
 
-                            let var_name = if let Expression::Identifier(name) = var_name_expr {
-                                name
-                            } else {
+                            // Every init sees the outer scope; the names only exist inside the body
+                            let let_env = Rc::new(RefCell::new(Environment::new_with_parent(env.clone())));
+                            let mut bound_names = HashSet::new();
+                            for (name, init_expr) in bindings {
+                                if !bound_names.insert(name) {
+                                    return Err(EvalError::SpecialFormError(format!(
+                                        "let binds '{}' more than once",
+                                        name
+                                    )));
+                                }
+                                let mut value = self.evaluate(init_expr, env.clone())?;
+                                name_lambda(&mut value, name);
+                                let_env.borrow_mut().define(name.clone(), value);
+                            }
+                            env = let_env;
+                            expr = self.eval_leading(&elements[2..], env.clone())?;
+                            continue;
+                        }
+                        Some("let") if elements.len() > 3 => {
+                            let Expression::Identifier(loop_name) = &elements[1] else {
                                 return Err(EvalError::TypeError(
-                                    "let expects an identifier as variable name".to_string(),
+                                    "named let expects an identifier as its name".to_string(),
                                 ));
                             };
+                            let bindings = parse_bindings("named let", &elements[2])?;
 
-                            let value = self.evaluate(value_expr, env.clone())?;
-                            env.borrow_mut().define(var_name.clone(), value);
-                            Ok(Value::Nil)
+                            let mut params = Vec::new();
+                            let mut args_values = Vec::new();
+                            for (name, init_expr) in bindings {
+                                params.push(name.clone());
+                                args_values.push(self.evaluate(init_expr, env.clone())?);
+                            }
+
+                            // The loop procedure lives in its own scope so its body can call it by name
+                            let loop_env = Rc::new(RefCell::new(Environment::new_with_parent(env.clone())));
+                            let loop_body: Rc<[Expression]> = elements[3..].into();
+                            let loop_proc = Value::Function(Rc::new(Callable::Lambda {
+                                name: Some(loop_name.clone()),
+                                params: params.clone(),
+                                rest: None,
+                                body: Rc::clone(&loop_body),
+                                env: Rc::clone(&loop_env),
+                            }));
+                            loop_env.borrow_mut().define(loop_name.clone(), loop_proc.clone());
+
+                            if self.is_traced(loop_name) {
+                                return self.apply(loop_proc, args_values);
+                            }
+                            env = bind_arguments(Some(loop_name), &params, None, &loop_env, args_values)?;
+                            tail_body = loop_body;
+                            expr = self.eval_leading(&tail_body, env.clone())?;
+                            continue;
+                        }
+                        Some("let") => self.eval_define(elements, env),
+                        Some("lambda") => self.eval_lambda(elements, env),
+                        Some("and") => {
+                            let Some((last, leading)) = elements[1..].split_last() else {
+                                return Ok(Value::Boolean(true));
+                            };
+                            for operand in leading {
+                                let result = self.evaluate(operand, env.clone())?;
+                                if !is_truthy(&result) {
+                                    return Ok(result);
+                                }
+                            }
+                            expr = last;
+                            continue;
                         }
-                        "lambda" => {
+                        Some("or") => {
+                            let Some((last, leading)) = elements[1..].split_last() else {
+                                return Ok(Value::Boolean(false));
+                            };
+                            for operand in leading {
+                                let result = self.evaluate(operand, env.clone())?;
+                                if is_truthy(&result) {
+                                    return Ok(result);
+                                }
+                            }
+                            expr = last;
+                            continue;
+                        }
+                        Some("do") => self.eval_do(elements, env),
+                        Some("fluid-let") => self.eval_fluid_let(elements, env),
+                        Some("letrec*") => {
                             if elements.len() < 3 {
                                 return Err(EvalError::WrongNumArgs(
-                                    "lambda expects at least (params) body".to_string(),
+                                    "letrec* expects ((var init) ...) body".to_string(),
                                 ));
                             }
-                            let params_expr = &elements[1];
-                            let body_exprs = elements[2..].to_vec();
-
-                            let params = if let Expression::List(param_list) = params_expr {
-                                param_list
-                                    .iter()
-                                    .map(|p_expr| {
-                                        if let Expression::Identifier(p_name) = p_expr {
-                                            Ok(p_name.clone())
-                                        } else {
-                                            Err(EvalError::TypeError(
-                                                "lambda parameters must be identifiers".to_string(),
-                                            ))
-                                        }
-                                    })
-                                    .collect::<Result<Vec<String>, EvalError>>()?
-                            } else {
-                                return Err(EvalError::TypeError(
-                                    "lambda parameters must be a list".to_string(),
-                                ));
-                            };
+                            let bindings = parse_bindings("letrec*", &elements[1])?;
 
-                            let captured_env = Rc::clone(&env);
-
-                            Ok(Value::Function(Rc::new(Callable::Lambda {
-                                params,
-                                body: body_exprs,
-                                env: captured_env,
-                            })))
+                            let letrec_env = Rc::new(RefCell::new(Environment::new_with_parent(env)));
+                            for (name, init_expr) in bindings {
+                                let mut value = self.evaluate(init_expr, letrec_env.clone())?;
+                                name_lambda(&mut value, name);
+                                letrec_env.borrow_mut().define(name.clone(), value);
+                            }
+                            env = letrec_env;
+                            expr = self.eval_leading(&elements[2..], env.clone())?;
+                            continue;
+                        }
+                        Some("assert") => self.eval_assert(elements, env),
+                        Some("ignore-errors") => self.eval_ignore_errors(elements, env),
+                        Some("unwind-protect") => self.eval_unwind_protect(elements, env),
+                        Some("define-condition-type") => self.eval_define_condition_type(elements, env),
+                        Some("time") => self.eval_time(elements, env),
+                        Some("the-environment") => {
+                            if elements.len() != 1 {
+                                return Err(EvalError::WrongNumArgs(
+                                    "the-environment expects no arguments".to_string(),
+                                ));
+                            }
+                            Ok(Value::EnvironmentRef(env))
                         }
+                        Some("environment-bindings") => self.eval_environment_bindings(elements, env),
                         _ => {
-                            self.apply_function_call(elements.to_vec(), env)
+                            let func_value = self.evaluate(&elements[0], env.clone())?;
+                            let args_values = self.eval_args(&elements[1..], env.clone())?;
+
+                            // Lambda bodies run in this loop; everything else goes through apply
+                            let Value::Function(callable) = &func_value else {
+                                return Err(EvalError::NotCallable(func_value));
+                            };
+                            let Callable::Lambda { name, params, rest, body, env: captured_env } = &**callable else {
+                                return self.apply(func_value, args_values);
+                            };
+                            // Traced calls must report their result, so they are not tail calls
+                            if name.as_ref().is_some_and(|name| self.is_traced(name)) {
+                                return self.apply(func_value, args_values);
+                            }
+
+                            env = bind_arguments(name.as_deref(), params, rest.as_ref(), captured_env, args_values)?;
+                            tail_body = Rc::clone(body);
+                            expr = self.eval_leading(&tail_body, env.clone())?;
+                            continue;
                         }
                     }
-                } else {
-                    self.apply_function_call(elements.to_vec(), env)
+                }
+            };
+        }
+    }
+
+    fn eval_define(&self, elements: &[Expression], env: Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+        if elements.len() != 3 {
+            return Err(EvalError::WrongNumArgs(
+                "let expects 2 arguments (variable value)".to_string(),
+            ));
+        }
+        let var_name_expr = &elements[1];
+        let value_expr = &elements[2];
+
+        let var_name = if let Expression::Identifier(name) = var_name_expr {
+            name
+        } else {
+            return Err(EvalError::TypeError(
+                "let expects an identifier as variable name".to_string(),
+            ));
+        };
+
+        let mut value = self.evaluate(value_expr, env.clone())?;
+        name_lambda(&mut value, var_name);
+        env.borrow_mut().define(var_name.clone(), value);
+        Ok(Value::Void)
+    }
+
+    fn eval_lambda(&self, elements: &[Expression], env: Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+        if elements.len() < 3 {
+            return Err(EvalError::WrongNumArgs(
+                "lambda expects at least (params) body".to_string(),
+            ));
+        }
+        let params_expr = &elements[1];
+        let body_exprs: Rc<[Expression]> = elements[2..].into();
+
+        let (params, rest) = parse_parameters(params_expr)?;
+
+        let captured_env = Rc::clone(&env);
+
+        Ok(Value::Function(Rc::new(Callable::Lambda {
+            name: None,
+            params,
+            rest,
+            body: body_exprs,
+            env: captured_env,
+        })))
+    }
+
+    fn eval_assert(&self, elements: &[Expression], env: Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+        if elements.len() < 2 || elements.len() > 3 {
+            return Err(EvalError::WrongNumArgs(
+                "assert expects 1 or 2 arguments (condition [message])".to_string(),
+            ));
+        }
+        let condition = self.evaluate(&elements[1], env.clone())?;
+        if is_truthy(&condition) {
+            return Ok(Value::Void);
+        }
+
+        let message = match elements.get(2) {
+            Some(message_expr) => self.evaluate(message_expr, env)?.to_string(),
+            None => Value::from_expression(&elements[1]).write_form(),
+        };
+        Err(EvalError::AssertionFailed(message))
+    }
+
+    fn eval_ignore_errors(&self, elements: &[Expression], env: Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+        if elements.len() != 2 {
+            return Err(EvalError::WrongNumArgs(
+                "ignore-errors expects 1 argument (expression)".to_string(),
+            ));
+        }
+        // Every error inside is caught here, so handlers outside must not see its raises
+        match builtins::without_exception_handlers(|| self.evaluate(&elements[1], env)) {
+            // Escapes are control flow rather than failures, so they pass through
+            Err(escape @ EvalError::Escape(_, _)) => Err(escape),
+            Err(_) => Ok(Value::Boolean(false)),
+            result => result,
+        }
+    }
+
+    fn eval_unwind_protect(&self, elements: &[Expression], env: Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+        if elements.len() < 3 {
+            return Err(EvalError::WrongNumArgs(
+                "unwind-protect expects a body and at least one cleanup expression".to_string(),
+            ));
+        }
+        let result = self.evaluate(&elements[1], env.clone());
+        // Cleanup runs on every exit, including escapes; its own errors are dropped
+        for cleanup in &elements[2..] {
+            let _ = self.evaluate(cleanup, env.clone());
+        }
+        result
+    }
+
+    fn eval_define_condition_type(&self, elements: &[Expression], env: Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+        if elements.len() != 3 {
+            return Err(EvalError::WrongNumArgs(
+                "define-condition-type expects 2 arguments (name (field ...))".to_string(),
+            ));
+        }
+        let Expression::Identifier(type_name) = &elements[1] else {
+            return Err(EvalError::TypeError(
+                "define-condition-type expects an identifier as type name".to_string(),
+            ));
+        };
+        let Expression::List(field_exprs) = &elements[2] else {
+            return Err(EvalError::TypeError(
+                "define-condition-type expects a list of field names".to_string(),
+            ));
+        };
+        let fields = field_exprs
+            .iter()
+            .map(|field| match field {
+                Expression::Identifier(field_name) => Ok(field_name.clone()),
+                _ => Err(EvalError::TypeError(
+                    "condition field names must be identifiers".to_string(),
+                )),
+            })
+            .collect::<Result<Vec<String>, EvalError>>()?;
+
+        self.condition_types.borrow_mut().insert(type_name.clone(), fields);
+        // The type name evaluates to itself so it can be passed to make-condition
+        env.borrow_mut().define(type_name.clone(), Value::Symbol(type_name.clone()));
+        Ok(Value::Void)
+    }
+
+    fn eval_time(&self, elements: &[Expression], env: Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+        if elements.len() != 2 {
+            return Err(EvalError::WrongNumArgs(
+                "time expects 1 argument (expression)".to_string(),
+            ));
+        }
+        let start = Instant::now();
+        let result = self.evaluate(&elements[1], env)?;
+        eprintln!("; Elapsed: {:.3}ms", start.elapsed().as_secs_f64() * 1000.0);
+        Ok(result)
+    }
+
+    fn eval_environment_bindings(&self, elements: &[Expression], env: Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+        if elements.len() != 1 {
+            return Err(EvalError::WrongNumArgs(
+                "environment-bindings expects no arguments".to_string(),
+            ));
+        }
+        let names = env
+            .borrow()
+            .keys()
+            .into_iter()
+            .map(Value::String)
+            .collect();
+        Ok(Value::list(names))
+    }
+
+    fn eval_do(&self, elements: &[Expression], env: Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+        if elements.len() < 3 {
+            return Err(EvalError::WrongNumArgs(
+                "do expects at least ((var init [step]) ...) (test expr ...)".to_string(),
+            ));
+        }
+
+        let Expression::List(var_specs) = &elements[1] else {
+            return Err(EvalError::TypeError(
+                "do expects a list of variable specs".to_string(),
+            ));
+        };
+        let Expression::List(test_clause) = &elements[2] else {
+            return Err(EvalError::TypeError(
+                "do expects a test clause (test expr ...)".to_string(),
+            ));
+        };
+        if test_clause.is_empty() {
+            return Err(EvalError::SpecialFormError(
+                "do test clause must contain a test expression".to_string(),
+            ));
+        }
+        let commands = &elements[3..];
+
+        let mut vars: Vec<(String, Option<&Expression>)> = Vec::new();
+        let loop_env = Rc::new(RefCell::new(Environment::new_with_parent(Rc::clone(&env))));
+        for spec in var_specs {
+            let spec_elements = match spec {
+                Expression::List(spec_elements) if spec_elements.len() == 2 || spec_elements.len() == 3 => spec_elements,
+                _ => {
+                    return Err(EvalError::SpecialFormError(
+                        "do variable spec must be (var init) or (var init step)".to_string(),
+                    ));
+                }
+            };
+            let Expression::Identifier(name) = &spec_elements[0] else {
+                return Err(EvalError::TypeError(
+                    "do expects an identifier as variable name".to_string(),
+                ));
+            };
+
+            let init = self.evaluate(&spec_elements[1], env.clone())?;
+            loop_env.borrow_mut().define(name.clone(), init);
+            vars.push((name.clone(), spec_elements.get(2)));
+        }
+
+        let mut current_env = loop_env;
+        loop {
+            let test = self.evaluate(&test_clause[0], current_env.clone())?;
+            if is_truthy(&test) {
+                let mut result = Value::Void;
+                for expr in &test_clause[1..] {
+                    result = self.evaluate(expr, current_env.clone())?;
+                }
+                return Ok(result);
+            }
+
+            for command in commands {
+                self.evaluate(command, current_env.clone())?;
+            }
+
+            // Steps are computed against the old bindings, then installed in a fresh scope
+            let next_env = Rc::new(RefCell::new(Environment::new_with_parent(Rc::clone(&env))));
+            for (name, step) in &vars {
+                let value = match step {
+                    Some(step_expr) => self.evaluate(step_expr, current_env.clone())?,
+                    None => current_env.borrow().get(name)?,
+                };
+                next_env.borrow_mut().define(name.clone(), value);
+            }
+            current_env = next_env;
+        }
+    }
+
+    fn eval_fluid_let(&self, elements: &[Expression], env: Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+        if elements.len() < 3 {
+            return Err(EvalError::WrongNumArgs(
+                "fluid-let expects ((var value) ...) body".to_string(),
+            ));
+        }
+        let bindings = parse_bindings("fluid-let", &elements[1])?;
+
+        let mut new_values = Vec::new();
+        for (name, value_expr) in bindings {
+            new_values.push((name, self.evaluate(value_expr, env.clone())?));
+        }
+
+        let mut saved = Vec::new();
+        let mut result = Ok(Value::Void);
+        for (name, value) in new_values {
+            let old_value = env.borrow().get(name);
+            match old_value {
+                Ok(old_value) => {
+                    env.borrow_mut().set(name.clone(), value)?;
+                    saved.push((name, old_value));
+                }
+                Err(e) => {
+                    result = Err(e);
+                    break;
                 }
             }
         }
+
+        if result.is_ok() {
+            result = self.eval_body(&elements[2..], env.clone());
+        }
+
+        // Restore the previous values whether the body returned normally or failed
+        for (name, old_value) in saved.into_iter().rev() {
+            env.borrow_mut().set(name.clone(), old_value)?;
+        }
+        result
+    }
+
+    fn eval_body(&self, body: &[Expression], env: Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+        let mut result = Value::Void;
+        for expr in body {
+            result = self.evaluate(expr, env.clone())?;
+        }
+        Ok(result)
+    }
+
+    // Evaluates all but the last expression of a body and returns the last one, which is in tail position
+    fn eval_leading<'e>(&self, body: &'e [Expression], env: Rc<RefCell<Environment>>) -> Result<&'e Expression, EvalError> {
+        let Some((last, leading)) = body.split_last() else {
+            return Err(EvalError::SpecialFormError("body must contain at least one expression".to_string()));
+        };
+        for expr in leading {
+            self.evaluate(expr, env.clone())?;
+        }
+        Ok(last)
     }
 
     fn eval_args(&self, args_exprs: &[Expression], env: Rc<RefCell<Environment>>) -> Result<Vec<Value>, EvalError> {
@@ -164,39 +695,55 @@ impl Evaluator {
             .collect()
     }
 
-    fn apply_function_call(&self, elements: Vec<Expression>, env: Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
-        let func_expr = &elements[0];
-        let args_exprs = &elements[1..];
+    fn is_traced(&self, name: &str) -> bool {
+        self.traced.borrow().contains(name)
+    }
+
+    pub fn apply(&self, func_value: Value, args_values: Vec<Value>) -> Result<Value, EvalError> {
+        let traced_name = match &func_value {
+            Value::Function(callable) => match &**callable {
+                Callable::Lambda { name: Some(name), .. } if self.is_traced(name) => Some(name.clone()),
+                _ => None,
+            },
+            _ => None,
+        };
 
-        let func_value = self.evaluate(func_expr, env.clone())?;
-        let args_values = self.eval_args(args_exprs, env.clone())?;
+        let Some(name) = traced_name else {
+            return self.apply_callable(func_value, args_values);
+        };
+        let call = Value::list(
+            std::iter::once(Value::Symbol(name.clone()))
+                .chain(args_values.iter().cloned())
+                .collect(),
+        );
+        eprintln!("[TRACE {}]", call.write_form());
+        let result = self.apply_callable(func_value, args_values)?;
+        eprintln!("[TRACE {} => {}]", name, result.write_form());
+        Ok(result)
+    }
 
+    fn apply_callable(&self, func_value: Value, args_values: Vec<Value>) -> Result<Value, EvalError> {
         if let Value::Function(callable_rc) = func_value {
             let callable = &*callable_rc;
 
             match callable {
                 Callable::Builtin(builtin_func) => builtin_func(args_values),
-                Callable::Lambda { params, body, env: captured_env } => {
-                    if args_values.len() != params.len() {
+                Callable::EvaluatorBuiltin(builtin_func) => builtin_func(self, args_values),
+                Callable::Escape(id) => {
+                    if args_values.len() != 1 {
                         return Err(EvalError::WrongNumArgs(format!(
-                            "Function expects {} arguments, but got {}",
-                            params.len(),
+                            "escape continuation expects 1 argument, but got {}",
                             args_values.len()
                         )));
                     }
-
-                    let func_call_env = Rc::new(RefCell::new(
-                        Environment::new_with_parent(Rc::clone(captured_env))
-                    ));
-
-                    for (param_name, arg_value) in params.iter().zip(args_values.into_iter()) {
-                        func_call_env
-                            .borrow_mut()
-                            .define(param_name.clone(), arg_value);
-                    }
+                    let value = args_values.into_iter().next().unwrap_or(Value::Void);
+                    Err(EvalError::Escape(*id, value))
+                }
+                Callable::Lambda { name, params, rest, body, env: captured_env } => {
+                    let func_call_env = bind_arguments(name.as_deref(), params, rest.as_ref(), captured_env, args_values)?;
 
                     let mut result = Value::Nil;
-                    for expr in body {
+                    for expr in body.iter() {
                         result = self.evaluate(expr, func_call_env.clone())?;
                     }
                     Ok(result)
@@ -216,4 +763,391 @@ impl Evaluator {
         }
         Ok(last_result)
     }
-}
\ No newline at end of file
+}
+
+// As in Scheme, every value except false counts as true
+pub(crate) fn is_truthy(value: &Value) -> bool {
+    !matches!(value, Value::Boolean(false))
+}
+
+// Gives a freshly created lambda the name it is being bound to; shared lambdas keep theirs
+fn name_lambda(value: &mut Value, name: &str) {
+    if let Value::Function(callable) = value
+        && let Some(Callable::Lambda { name: lambda_name @ None, .. }) = Rc::get_mut(callable)
+    {
+        *lambda_name = Some(name.to_string());
+    }
+}
+
+// Reads a lambda list: (a b), (a b . rest), or a bare identifier that collects every argument
+fn parse_parameters(params_expr: &Expression) -> Result<(Vec<String>, Option<String>), EvalError> {
+    let param_list = match params_expr {
+        Expression::Identifier(rest) => return Ok((Vec::new(), Some(rest.clone()))),
+        Expression::List(param_list) => param_list,
+        _ => {
+            return Err(EvalError::TypeError(
+                "lambda parameters must be a list or an identifier".to_string(),
+            ));
+        }
+    };
+
+    let names = param_list
+        .iter()
+        .map(|p_expr| {
+            if let Expression::Identifier(p_name) = p_expr {
+                Ok(p_name.clone())
+            } else {
+                Err(EvalError::TypeError(
+                    "lambda parameters must be identifiers".to_string(),
+                ))
+            }
+        })
+        .collect::<Result<Vec<String>, EvalError>>()?;
+
+    match names.iter().position(|name| name == ".") {
+        None => Ok((names, None)),
+        Some(dot) if dot + 2 == names.len() && names[dot + 1] != "." => {
+            let mut params = names;
+            let rest = params.pop();
+            params.pop();
+            Ok((params, rest))
+        }
+        Some(_) => Err(EvalError::SpecialFormError(
+            "'.' in a lambda list must be followed by exactly one rest parameter".to_string(),
+        )),
+    }
+}
+
+// Creates the scope for a lambda call, binding each parameter to its argument
+fn bind_arguments(
+    name: Option<&str>,
+    params: &[String],
+    rest: Option<&String>,
+    captured_env: &Rc<RefCell<Environment>>,
+    args_values: Vec<Value>,
+) -> Result<Rc<RefCell<Environment>>, EvalError> {
+    let func_name = name.unwrap_or("Function");
+    match rest {
+        None if args_values.len() != params.len() => {
+            return Err(EvalError::WrongNumArgs(format!(
+                "{} expects {} arguments, but got {}",
+                func_name,
+                params.len(),
+                args_values.len()
+            )));
+        }
+        Some(_) if args_values.len() < params.len() => {
+            return Err(EvalError::WrongNumArgs(format!(
+                "{} expects at least {} arguments, but got {}",
+                func_name,
+                params.len(),
+                args_values.len()
+            )));
+        }
+        _ => {}
+    }
+
+    let func_call_env = Rc::new(RefCell::new(
+        Environment::new_with_parent(Rc::clone(captured_env))
+    ));
+
+    let mut args_values = args_values.into_iter();
+    for (param_name, arg_value) in params.iter().zip(args_values.by_ref()) {
+        func_call_env
+            .borrow_mut()
+            .define(param_name.clone(), arg_value);
+    }
+    if let Some(rest_name) = rest {
+        func_call_env
+            .borrow_mut()
+            .define(rest_name.clone(), Value::list(args_values.collect()));
+    }
+    Ok(func_call_env)
+}
+
+// Splits a ((name init) ...) binding list into its names and init expressions
+fn parse_bindings<'a>(form: &str, expr: &'a Expression) -> Result<Vec<(&'a String, &'a Expression)>, EvalError> {
+    let Expression::List(bindings) = expr else {
+        return Err(EvalError::TypeError(format!("{} expects a list of bindings", form)));
+    };
+
+    bindings
+        .iter()
+        .map(|binding| match binding {
+            Expression::List(pair) if pair.len() == 2 => match &pair[0] {
+                Expression::Identifier(name) => Ok((name, &pair[1])),
+                _ => Err(EvalError::TypeError(format!(
+                    "{} expects an identifier as variable name",
+                    form
+                ))),
+            },
+            _ => Err(EvalError::SpecialFormError(format!(
+                "{} binding must be (variable value)",
+                form
+            ))),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{eval, numbers, parse};
+    use std::thread;
+
+    #[test]
+    fn registered_builtins_and_values_are_visible_to_lisp() {
+        let evaluator = Evaluator::new();
+        evaluator.register_builtin("double", |args| match args.as_slice() {
+            [Value::Number(n)] => Ok(Value::Number(n * 2.0)),
+            _ => Err(EvalError::TypeError("double expects a number".to_string())),
+        });
+        evaluator.register_value("answer", Value::Number(21.0));
+        assert_eq!(evaluator.eval_program(&parse("(double answer)")), Ok(Value::Number(42.0)));
+    }
+
+    #[test]
+    fn environment_bindings_lists_the_current_scope() {
+        let result = eval("(let f (lambda (a) (environment-bindings))) (f 1)");
+        assert_eq!(result, Ok(Value::list(vec![Value::String("a".to_string())])));
+    }
+
+    #[test]
+    fn defining_let_returns_void() {
+        assert_eq!(eval("(let x 1)"), Ok(Value::Void));
+        assert_eq!(eval("(if false 1)"), Ok(Value::Void));
+    }
+
+    #[test]
+    fn do_loop_steps_until_the_test_holds() {
+        let result = eval("(do ((i 0 (+ i 1)) (acc 0 (+ acc i))) ((= i 5) acc))");
+        assert_eq!(result, Ok(Value::Number(10.0)));
+    }
+
+    #[test]
+    fn time_returns_the_value_of_its_expression() {
+        assert_eq!(eval("(time (+ 1 2))"), Ok(Value::Number(3.0)));
+        assert_eq!(eval("(> (current-time) 0)"), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn assert_reports_the_failed_condition() {
+        assert_eq!(eval("(assert (= 1 1))"), Ok(Value::Void));
+        assert_eq!(eval("(assert (= 1 2))"), Err(EvalError::AssertionFailed("(= 1 2)".to_string())));
+        assert_eq!(eval("(assert false \"custom\")"), Err(EvalError::AssertionFailed("custom".to_string())));
+    }
+
+    #[test]
+    fn letrec_star_allows_mutual_recursion() {
+        let result = eval(
+            "(letrec* ((even? (lambda (n) (if (= n 0) true (odd? (- n 1)))))
+                       (odd? (lambda (n) (if (= n 0) false (even? (- n 1))))))
+               (even? 10))",
+        );
+        assert_eq!(result, Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn fluid_let_restores_the_old_value() {
+        let result = eval(
+            "(let x 1)
+             (let get-x (lambda () x))
+             (list (fluid-let ((x 2)) (get-x)) (get-x))",
+        );
+        assert_eq!(result, Ok(numbers(&[2.0, 1.0])));
+    }
+
+    #[test]
+    fn escape_continuation_returns_early() {
+        assert_eq!(eval("(call/ec (lambda (k) (+ 1 (k 42))))"), Ok(Value::Number(42.0)));
+        assert_eq!(eval("(call/ec (lambda (k) 7))"), Ok(Value::Number(7.0)));
+    }
+
+    #[test]
+    fn and_or_return_the_deciding_value() {
+        assert_eq!(eval("(and 1 2 3)"), Ok(Value::Number(3.0)));
+        assert_eq!(eval("(and 1 false 3)"), Ok(Value::Boolean(false)));
+        assert_eq!(eval("(or false 2 3)"), Ok(Value::Number(2.0)));
+        assert_eq!(eval("(and)"), Ok(Value::Boolean(true)));
+        assert_eq!(eval("(or)"), Ok(Value::Boolean(false)));
+    }
+
+    #[test]
+    fn tail_calls_run_in_constant_stack() {
+        let result = eval(
+            "(let count-down (lambda (n) (if (= n 0) (done) (count-down (- n 1)))))
+             (let done (lambda () 0))
+             (count-down 100000)",
+        );
+        assert_eq!(result, Ok(Value::Number(0.0)));
+    }
+
+    #[test]
+    fn mutual_recursion_through_and_or_is_a_tail_call() {
+        let result = eval(
+            "(let even? (lambda (n) (or (= n 0) (and true (odd? (- n 1))))))
+             (let odd? (lambda (n) (and (!= n 0) (even? (- n 1)))))
+             (even? 100000)",
+        );
+        assert_eq!(result, Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn depth_limit_is_configurable() {
+        let evaluator = Evaluator::with_max_depth(50);
+        let program = parse("(let f (lambda (n) (if (= n 0) 0 (+ 1 (f (- n 1)))))) (f 100)");
+        assert_eq!(evaluator.eval_program(&program), Err(EvalError::StackOverflow));
+    }
+
+    #[test]
+    fn recursion_past_the_stack_limit_is_an_error_on_a_default_thread() {
+        let overflowed = thread::spawn(|| {
+            let result = eval("(let f (lambda (n) (+ 1 (f (+ n 1))))) (f 0)");
+            matches!(result, Err(EvalError::StackOverflow))
+        });
+        assert!(overflowed.join().expect("evaluation should not crash the thread"));
+    }
+
+    #[test]
+    fn trace_does_not_change_the_result() {
+        assert_eq!(eval("(let sq (lambda (x) (* x x))) (trace sq) (sq 3)"), Ok(Value::Number(9.0)));
+    }
+
+    #[test]
+    fn ignore_errors_turns_failures_into_false() {
+        assert_eq!(eval("(ignore-errors (car 1))"), Ok(Value::Boolean(false)));
+        assert_eq!(eval("(ignore-errors (+ 1 2))"), Ok(Value::Number(3.0)));
+        assert_eq!(eval("(call/ec (lambda (k) (ignore-errors (k 5))))"), Ok(Value::Number(5.0)));
+    }
+
+    #[test]
+    fn unwind_protect_runs_cleanup_on_error() {
+        let result = eval(
+            "(let cleaned false)
+             (ignore-errors (unwind-protect (car 1) (let cleaned true)))
+             cleaned",
+        );
+        assert_eq!(result, Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn condition_types_carry_their_fields() {
+        let result = eval(
+            "(define-condition-type file-error (path))
+             (let c (make-condition file-error \"missing\" (read-from-string \"path\") \"/tmp/x\"))
+             (list (condition/ref c (read-from-string \"path\")) (condition/message c))",
+        );
+        assert_eq!(
+            result,
+            Ok(Value::list(vec![Value::String("/tmp/x".to_string()), Value::String("missing".to_string())]))
+        );
+    }
+
+    #[test]
+    fn multi_binding_let_evaluates_inits_in_the_outer_scope() {
+        assert_eq!(eval("(let x 1) (let ((x 2) (y x)) (+ x y))"), Ok(Value::Number(3.0)));
+        assert!(matches!(eval("(let ((x 1) (x 2)) x)"), Err(EvalError::SpecialFormError(_))));
+    }
+
+    #[test]
+    fn let_bodies_are_tail_positions() {
+        let result = eval(
+            "(let loop (lambda (n)
+               (let ((m (- n 1)))
+                 (or (= n 0) (loop m)))))
+             (loop 100000)",
+        );
+        assert_eq!(result, Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn rest_parameters_collect_extra_arguments() {
+        assert_eq!(eval("((lambda (a . rest) rest) 1 2 3)"), Ok(numbers(&[2.0, 3.0])));
+        assert_eq!(eval("((lambda args args) 1 2)"), Ok(numbers(&[1.0, 2.0])));
+        assert!(matches!(eval("((lambda (a b . rest) a) 1)"), Err(EvalError::WrongNumArgs(_))));
+    }
+
+    #[test]
+    fn named_let_loops() {
+        let result = eval("(let loop ((i 3) (acc (list))) (if (= i 0) acc (loop (- i 1) (cons (- i 1) acc))))");
+        assert_eq!(result, Ok(numbers(&[0.0, 1.0, 2.0])));
+    }
+
+    #[test]
+    fn stdlib_procedures_are_loaded() {
+        assert_eq!(eval("(map (lambda (x) (* x x)) (list 1 2 3))"), Ok(numbers(&[1.0, 4.0, 9.0])));
+        assert_eq!(eval("(filter (lambda (x) (> x 1)) (list 1 2 3))"), Ok(numbers(&[2.0, 3.0])));
+        assert_eq!(eval("(fold-left + 0 (list 1 2 3))"), Ok(Value::Number(6.0)));
+        assert_eq!(eval("(list (null? (list)) (pair? (list)) (pair? (cons 1 2)))"),
+            Ok(Value::list(vec![Value::Boolean(true), Value::Boolean(false), Value::Boolean(true)])));
+    }
+
+    #[test]
+    fn eval_runs_data_in_a_captured_environment() {
+        let result = eval(
+            "(let make-env (lambda (x) (the-environment)))
+             (eval (read-from-string \"(* x 2)\") (make-env 21))",
+        );
+        assert_eq!(result, Ok(Value::Number(42.0)));
+    }
+
+    #[test]
+    fn dynamic_wind_runs_before_and_after() {
+        let result = eval(
+            "(let log (list 0))
+             (let note (lambda (x) (set-cdr! log (cons x (cdr log)))))
+             (call/ec (lambda (k)
+               (dynamic-wind (lambda () (note 1)) (lambda () (k 0)) (lambda () (note 2)))))
+             (reverse (cdr log))",
+        );
+        assert_eq!(result, Ok(numbers(&[1.0, 2.0])));
+    }
+
+    #[test]
+    fn map_accepts_several_lists() {
+        assert_eq!(eval("(map + (list 1 2) (list 10 20))"), Ok(numbers(&[11.0, 22.0])));
+        assert_eq!(eval("(map list (list 1 2) (list 3 4) (list 5 6))"),
+            Ok(Value::list(vec![numbers(&[1.0, 3.0, 5.0]), numbers(&[2.0, 4.0, 6.0])])));
+        assert_eq!(
+            eval("(map + (list 1 2) (list 10))"),
+            Err(EvalError::WrongNumArgs("map: lists have different lengths".to_string()))
+        );
+    }
+
+    #[test]
+    fn apply_spreads_its_last_argument() {
+        assert_eq!(eval("(apply + 1 (list 2 3))"), Ok(Value::Number(6.0)));
+    }
+
+    #[test]
+    fn for_each_accepts_several_lists() {
+        let result = eval(
+            "(let total (list 0))
+             (for-each (lambda (a b) (set-car! total (+ (car total) a b))) (list 1 2) (list 10 20))
+             (car total)",
+        );
+        assert_eq!(result, Ok(Value::Number(33.0)));
+        assert_eq!(
+            eval("(for-each + (list 1 2) (list 10))"),
+            Err(EvalError::WrongNumArgs("for-each: lists have different lengths".to_string()))
+        );
+    }
+
+    #[test]
+    fn list_index_finds_the_first_match() {
+        assert_eq!(eval("(list-index (lambda (x) (> x 1)) (list 1 2 3))"), Ok(Value::Number(1.0)));
+        assert_eq!(eval("(list-index (lambda (x) (> x 5)) (list 1 2 3))"), Ok(Value::Boolean(false)));
+    }
+
+    #[test]
+    fn named_lambdas_show_their_name() {
+        assert_eq!(
+            eval("(let sq (lambda (x) (* x x))) (with-output-to-string (lambda () (print* sq)))"),
+            Ok(Value::String("#<lambda sq (x)>".to_string()))
+        );
+        assert_eq!(
+            eval("(let sq (lambda (x) (* x x))) (sq 1 2)"),
+            Err(EvalError::WrongNumArgs("sq expects 1 arguments, but got 2".to_string()))
+        );
+    }
+}
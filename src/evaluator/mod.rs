@@ -4,5 +4,5 @@ pub mod environment;
 pub mod value;
 
 pub use self::evaluator::{Evaluator, EvalError};
-pub use self::value::{Value, Callable};
+pub use self::value::{Value, Callable, BuiltinFn, EvaluatorBuiltinFn};
 pub use self::environment::Environment;
\ No newline at end of file
@@ -1,46 +1,534 @@
 use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::rc::Rc;
 
 use crate::ast::Expression;
-use crate::evaluator::{Environment, EvalError};
+use crate::evaluator::{Environment, EvalError, Evaluator};
 
-#[derive(Debug, Clone, PartialEq)]
+pub type BuiltinFn = fn(Vec<Value>) -> Result<Value, EvalError>;
+pub type EvaluatorBuiltinFn = fn(&Evaluator, Vec<Value>) -> Result<Value, EvalError>;
+
+#[derive(Debug, Clone)]
 pub enum Callable {
-    Builtin(fn(Vec<Value>) -> Result<Value, EvalError>),
+    Builtin(BuiltinFn),
+    EvaluatorBuiltin(EvaluatorBuiltinFn), // A builtin that calls back into the evaluator
+    Escape(usize),                        // Escape continuation of the call/ec with this id
     Lambda {
+        name: Option<String>, // Set when a fresh lambda is bound with let or letrec*
         params: Vec<String>,
-        body: Vec<Expression>,
+        rest: Option<String>, // Parameter bound to the list of any extra arguments
+        body: Rc<[Expression]>,
         env: Rc<RefCell<Environment>>,
     },
 }
 
+// Lambdas compare by identity: a global lambda's environment contains the lambda itself,
+// so comparing environments structurally would never terminate
+impl PartialEq for Callable {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Callable::Builtin(a), Callable::Builtin(b)) => std::ptr::fn_addr_eq(*a, *b),
+            (Callable::EvaluatorBuiltin(a), Callable::EvaluatorBuiltin(b)) => std::ptr::fn_addr_eq(*a, *b),
+            (Callable::Escape(a), Callable::Escape(b)) => a == b,
+            (Callable::Lambda { body: b1, env: e1, .. }, Callable::Lambda { body: b2, env: e2, .. }) => {
+                Rc::ptr_eq(b1, b2) && Rc::ptr_eq(e1, e2)
+            }
+            _ => false,
+        }
+    }
+}
+
 impl fmt::Display for Callable {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Callable::Builtin(_) => write!(f, "#<builtin-function>"),
-            Callable::Lambda { params, .. } => write!(f, "#<lambda ({})>", params.join(" ")),
+            Callable::Builtin(_) | Callable::EvaluatorBuiltin(_) => write!(f, "#<builtin-function>"),
+            Callable::Escape(_) => write!(f, "#<escape-continuation>"),
+            Callable::Lambda { name: Some(name), params, rest, .. } => {
+                write!(f, "#<lambda {} {}>", name, format_parameters(params, rest.as_deref()))
+            }
+            Callable::Lambda { name: None, params, rest, .. } => {
+                write!(f, "#<lambda {}>", format_parameters(params, rest.as_deref()))
+            }
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+// Renders a lambda list the way it is written: (a b), (a b . rest) or a bare rest name
+pub fn format_parameters(params: &[String], rest: Option<&str>) -> String {
+    match rest {
+        None => format!("({})", params.join(" ")),
+        Some(rest) if params.is_empty() => rest.to_string(),
+        Some(rest) => format!("({} . {})", params.join(" "), rest),
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum Value {
     Number(f64),            // Floating-point numbers (e.g., 10, 3.14)
     String(String),         // Text strings (e.g., "hello world")
     Boolean(bool),          // Boolean values (true or false)
-    Nil,                    // Represents Lisp's 'null' or 'void' value
+    Nil,                    // Represents Lisp's 'null' value, also the empty list
+    Void,                   // Result of side-effecting forms that produce no value
+    Eof,                    // End-of-file object returned by readers with nothing left to read
+    Symbol(String),         // A symbol produced by the reader (e.g., foo)
     Function(Rc<Callable>), // A callable function (built-in or lambda)
+    Pair(Rc<RefCell<(Value, Value)>>), // A cons cell; lists are chains of pairs ending in Nil
+    ErrorObject {                      // Condition created by (error message irritant ...)
+        message: String,
+        irritants: Vec<Value>,
+    },
+    Condition {                        // Instance of a type declared with define-condition-type
+        type_name: String,
+        message: String,
+        fields: HashMap<String, Value>,
+    },
+    EnvironmentRef(Rc<RefCell<Environment>>), // A scope captured by (the-environment)
+    Bytevector(Rc<RefCell<Vec<u8>>>),        // Mutable sequence of bytes (e.g., #u8(1 2 3))
+    StringBuilder(Rc<RefCell<String>>),      // Growable buffer filled by string-builder-append!
+}
+
+impl Value {
+    pub fn cons(car: Value, cdr: Value) -> Value {
+        Value::Pair(Rc::new(RefCell::new((car, cdr))))
+    }
+
+    pub fn from_expression(expr: &Expression) -> Value {
+        match expr {
+            Expression::Number(n) => Value::Number(*n),
+            Expression::String(s) => Value::String(s.clone()),
+            Expression::Boolean(b) => Value::Boolean(*b),
+            Expression::Identifier(name) => Value::Symbol(name.clone()),
+            Expression::List(elements) => {
+                Value::list(elements.iter().map(Value::from_expression).collect())
+            }
+        }
+    }
+
+    // The inverse of from_expression, used by eval to run data as code
+    pub fn to_expression(&self) -> Result<Expression, EvalError> {
+        match self {
+            Value::Number(n) => Ok(Expression::Number(*n)),
+            Value::String(s) => Ok(Expression::String(s.clone())),
+            Value::Boolean(b) => Ok(Expression::Boolean(*b)),
+            Value::Symbol(name) => Ok(Expression::Identifier(name.clone())),
+            Value::Nil | Value::Pair(_) => {
+                let elements = self
+                    .clone()
+                    .into_vec()?
+                    .iter()
+                    .map(Value::to_expression)
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Expression::List(elements))
+            }
+            other => Err(EvalError::TypeError(format!(
+                "cannot evaluate {} as an expression",
+                other.write_form()
+            ))),
+        }
+    }
+
+    pub fn write_form(&self) -> String {
+        Written(self).to_string()
+    }
+
+    pub fn list(items: Vec<Value>) -> Value {
+        items
+            .into_iter()
+            .rev()
+            .fold(Value::Nil, |tail, item| Value::cons(item, tail))
+    }
+
+    pub fn into_vec(self) -> Result<Vec<Value>, EvalError> {
+        let mut items = Vec::new();
+        let mut current = self;
+        // Moves one pair for every two taken by current; they can only meet again on a cycle
+        let mut tortoise = current.clone();
+        loop {
+            let next = match &current {
+                Value::Nil => return Ok(items),
+                Value::Pair(pair) => {
+                    let (car, cdr) = pair.borrow().clone();
+                    items.push(car);
+                    cdr
+                }
+                _ if items.is_empty() => {
+                    return Err(EvalError::TypeError(format!(
+                        "expected a proper list, got {}",
+                        current.write_form()
+                    )));
+                }
+                _ => {
+                    return Err(EvalError::TypeError(format!(
+                        "expected a proper list, but the list ends in {}",
+                        current.write_form()
+                    )));
+                }
+            };
+            current = next;
+
+            if items.len() % 2 == 0 && let Value::Pair(pair) = &tortoise {
+                let next = pair.borrow().1.clone();
+                tortoise = next;
+            }
+            if let (Value::Pair(a), Value::Pair(b)) = (&tortoise, &current)
+                && Rc::ptr_eq(a, b)
+            {
+                return Err(EvalError::TypeError("expected a proper list, got a circular list".to_string()));
+            }
+        }
+    }
+
+    // True when printing the value needs datum labels because some pair contains itself
+    pub fn is_circular(&self) -> bool {
+        !cycle_targets(self).is_empty()
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(items: Vec<Value>) -> Self {
+        Value::list(items)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Number(n)
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Boolean(b)
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = EvalError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Number(n) => Ok(n),
+            other => Err(EvalError::TypeError(format!("expected a number, got {}", other.write_form()))),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = EvalError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::String(s) => Ok(s),
+            other => Err(EvalError::TypeError(format!("expected a string, got {}", other.write_form()))),
+        }
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = EvalError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Boolean(b) => Ok(b),
+            other => Err(EvalError::TypeError(format!("expected a boolean, got {}", other.write_form()))),
+        }
+    }
+}
+
+type PairCell = RefCell<(Value, Value)>;
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        values_equal(self, other, &mut HashSet::new())
+    }
+}
+
+// Compares structurally. Two cells that are met again while still being compared are taken as
+// equal, so circular lists compare in finite time; cdr chains are followed in a loop, not recursively
+fn values_equal(a: &Value, b: &Value, seen: &mut HashSet<(*const PairCell, *const PairCell)>) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x == y,
+        (Value::String(x), Value::String(y)) => x == y,
+        (Value::Boolean(x), Value::Boolean(y)) => x == y,
+        (Value::Nil, Value::Nil) | (Value::Void, Value::Void) | (Value::Eof, Value::Eof) => true,
+        (Value::Symbol(x), Value::Symbol(y)) => x == y,
+        (Value::Function(x), Value::Function(y)) => x == y,
+        (Value::Pair(x), Value::Pair(y)) => {
+            let (mut x, mut y) = (x.clone(), y.clone());
+            loop {
+                if !seen.insert((Rc::as_ptr(&x), Rc::as_ptr(&y))) {
+                    return true;
+                }
+                let (car_x, cdr_x) = x.borrow().clone();
+                let (car_y, cdr_y) = y.borrow().clone();
+                if !values_equal(&car_x, &car_y, seen) {
+                    return false;
+                }
+                match (cdr_x, cdr_y) {
+                    (Value::Pair(next_x), Value::Pair(next_y)) => (x, y) = (next_x, next_y),
+                    (cdr_x, cdr_y) => return values_equal(&cdr_x, &cdr_y, seen),
+                }
+            }
+        }
+        (
+            Value::ErrorObject { message: m1, irritants: i1 },
+            Value::ErrorObject { message: m2, irritants: i2 },
+        ) => m1 == m2 && i1.len() == i2.len() && i1.iter().zip(i2).all(|(x, y)| values_equal(x, y, seen)),
+        (
+            Value::Condition { type_name: t1, message: m1, fields: f1 },
+            Value::Condition { type_name: t2, message: m2, fields: f2 },
+        ) => {
+            t1 == t2
+                && m1 == m2
+                && f1.len() == f2.len()
+                && f1.iter().all(|(name, x)| f2.get(name).is_some_and(|y| values_equal(x, y, seen)))
+        }
+        // An environment can hold a reference to itself, so environments compare by identity
+        (Value::EnvironmentRef(x), Value::EnvironmentRef(y)) => Rc::ptr_eq(x, y),
+        (Value::Bytevector(x), Value::Bytevector(y)) => x == y,
+        (Value::StringBuilder(x), Value::StringBuilder(y)) => x == y,
+        _ => false,
+    }
 }
 
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
+        fmt_value(self, f, false)
+    }
+}
+
+// Formats a value the way `write` prints it, so that the reader can reconstruct it
+struct Written<'a>(&'a Value);
+
+impl fmt::Display for Written<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_value(self.0, f, true)
+    }
+}
+
+fn fmt_value(value: &Value, f: &mut fmt::Formatter<'_>, written: bool) -> fmt::Result {
+    let labels = cycle_targets(value).into_iter().map(|pair| (pair, None)).collect();
+    ValuePrinter { written, labels, next_label: 0 }.fmt(value, f)
+}
+
+// Finds the pairs that a walk through cars and cdrs reaches again while still inside them. Only
+// these need a #n= label for printing to terminate; shared structure without a cycle prints twice.
+fn cycle_targets(value: &Value) -> HashSet<*const PairCell> {
+    enum Step {
+        Enter(Value),
+        Leave(*const PairCell),
+    }
+
+    let mut targets = HashSet::new();
+    let mut in_progress = HashMap::new(); // true until everything reachable from the pair is walked
+    let mut stack = vec![Step::Enter(value.clone())];
+    while let Some(step) = stack.pop() {
+        match step {
+            Step::Enter(Value::Pair(pair)) => {
+                let ptr = Rc::as_ptr(&pair);
+                match in_progress.get(&ptr) {
+                    Some(true) => {
+                        targets.insert(ptr);
+                    }
+                    Some(false) => {}
+                    None => {
+                        in_progress.insert(ptr, true);
+                        let (car, cdr) = pair.borrow().clone();
+                        stack.push(Step::Leave(ptr));
+                        stack.push(Step::Enter(cdr));
+                        stack.push(Step::Enter(car));
+                    }
+                }
+            }
+            Step::Enter(Value::ErrorObject { irritants, .. }) => {
+                stack.extend(irritants.into_iter().rev().map(Step::Enter));
+            }
+            Step::Enter(_) => {}
+            Step::Leave(ptr) => {
+                in_progress.insert(ptr, false);
+            }
+        }
+    }
+    targets
+}
+
+// Prints values, writing the pairs on a cycle with datum labels: #0=(1 2 . #0#)
+struct ValuePrinter {
+    written: bool,
+    labels: HashMap<*const PairCell, Option<usize>>, // Number assigned once the pair is printed
+    next_label: usize,
+}
+
+impl ValuePrinter {
+    fn fmt(&mut self, value: &Value, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match value {
+            Value::Number(n) if n.is_nan() => write!(f, "+nan.0"),
+            Value::Number(n) if n.is_infinite() => write!(f, "{}inf.0", if *n > 0.0 { "+" } else { "-" }),
             Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) if self.written => {
+                write!(f, "\"")?;
+                for c in s.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\t' => write!(f, "\\t")?,
+                        _ => write!(f, "{}", c)?,
+                    }
+                }
+                write!(f, "\"")
+            }
             Value::String(s) => write!(f, "{}", s),
             Value::Boolean(b) => write!(f, "{}", b),
             Value::Nil => write!(f, "nil"),
+            Value::Void => write!(f, "#<void>"),
+            Value::Eof => write!(f, "#<eof>"),
+            Value::Symbol(name) => write!(f, "{}", name),
             Value::Function(func) => write!(f, "{}", func),
+            Value::ErrorObject { message, irritants } => {
+                write!(f, "#<error {}", Written(&Value::String(message.clone())))?;
+                let written = std::mem::replace(&mut self.written, true);
+                for irritant in irritants {
+                    write!(f, " ")?;
+                    self.fmt(irritant, f)?;
+                }
+                self.written = written;
+                write!(f, ">")
+            }
+            Value::Condition { type_name, message, .. } => {
+                write!(f, "#<condition {} {}>", type_name, Written(&Value::String(message.clone())))
+            }
+            Value::EnvironmentRef(_) => write!(f, "#<environment>"),
+            Value::StringBuilder(_) => write!(f, "#<string-builder>"),
+            Value::Bytevector(bytes) => {
+                let bytes: Vec<String> = bytes.borrow().iter().map(|byte| byte.to_string()).collect();
+                write!(f, "#u8({})", bytes.join(" "))
+            }
+            Value::Pair(pair) => self.fmt_pair(pair, f),
+        }
+    }
+
+    fn fmt_pair(&mut self, pair: &Rc<PairCell>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.labels.get_mut(&Rc::as_ptr(pair)) {
+            Some(Some(label)) => return write!(f, "#{}#", label),
+            Some(label) => {
+                *label = Some(self.next_label);
+                write!(f, "#{}=", self.next_label)?;
+                self.next_label += 1;
+            }
+            None => {}
+        }
+
+        let (car, cdr) = pair.borrow().clone();
+        write!(f, "(")?;
+        self.fmt(&car, f)?;
+
+        let mut rest = cdr;
+        loop {
+            rest = match rest {
+                Value::Nil => break,
+                // A labelled pair in the tail is printed after a dot so that its label can be written
+                Value::Pair(next) if !self.labels.contains_key(&Rc::as_ptr(&next)) => {
+                    let (car, cdr) = next.borrow().clone();
+                    write!(f, " ")?;
+                    self.fmt(&car, f)?;
+                    cdr
+                }
+                other => {
+                    write!(f, " . ")?;
+                    self.fmt(&other, f)?;
+                    break;
+                }
+            };
         }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::eval;
+
+    #[test]
+    fn written_form_quotes_strings() {
+        let value = Value::list(vec![Value::String("a".to_string()), Value::Number(1.5)]);
+        assert_eq!(value.to_string(), "(a 1.5)");
+        assert_eq!(value.write_form(), "(\"a\" 1.5)");
+    }
+
+    #[test]
+    fn converts_to_and_from_rust_primitives() {
+        assert_eq!(Value::from(1.5), Value::Number(1.5));
+        assert_eq!(Value::from(true), Value::Boolean(true));
+        assert_eq!(Value::from("a".to_string()), Value::String("a".to_string()));
+        assert_eq!(f64::try_from(Value::Number(2.0)), Ok(2.0));
+        assert!(f64::try_from(Value::Boolean(true)).is_err());
+        assert_eq!(String::try_from(Value::String("s".to_string())), Ok("s".to_string()));
+        assert_eq!(bool::try_from(Value::Boolean(false)), Ok(false));
+    }
+
+    #[test]
+    fn lists_convert_to_and_from_vectors() {
+        let list = Value::from(vec![Value::Number(1.0), Value::Number(2.0)]);
+        assert_eq!(list.to_string(), "(1 2)");
+        assert_eq!(list.into_vec(), Ok(vec![Value::Number(1.0), Value::Number(2.0)]));
+        assert!(Value::cons(Value::Number(1.0), Value::Number(2.0)).into_vec().is_err());
+    }
+
+    #[test]
+    fn improper_lists_print_with_a_dot() {
+        assert_eq!(Value::cons(Value::Number(1.0), Value::Number(2.0)).to_string(), "(1 . 2)");
+        let list = Value::cons(Value::Number(1.0), Value::cons(Value::Number(2.0), Value::Number(3.0)));
+        assert_eq!(list.to_string(), "(1 2 . 3)");
+    }
+
+    #[test]
+    fn infinities_print_in_lisp_syntax() {
+        let value = Value::list(vec![Value::Number(f64::INFINITY), Value::Number(f64::NEG_INFINITY)]);
+        assert_eq!(value.to_string(), "(+inf.0 -inf.0)");
+        assert_eq!(Value::Number(f64::NAN).write_form(), "+nan.0");
+    }
+
+    #[test]
+    fn lambdas_compare_by_identity() {
+        assert_eq!(eval("(let f (lambda () 1)) (= f f)"), Ok(Value::Boolean(true)));
+        assert_eq!(eval("(let f (lambda () 1)) (let g f) (= f g)"), Ok(Value::Boolean(true)));
+        assert_eq!(eval("(let f (lambda () 1)) (= f (lambda () 1))"), Ok(Value::Boolean(false)));
+    }
+
+    #[test]
+    fn circular_lists_compare_without_looping() {
+        let two = "(let a (list 1 2)) (set-cdr! (cdr a) a)";
+        assert_eq!(eval(&format!("{} (= a a)", two)), Ok(Value::Boolean(true)));
+        let four = "(let b (list 1 2 1 2)) (set-cdr! (cdr (cdr (cdr b))) b)";
+        assert_eq!(eval(&format!("{} {} (= a b)", two, four)), Ok(Value::Boolean(true)));
+        let other = "(let b (list 1 3)) (set-cdr! (cdr b) b)";
+        assert_eq!(eval(&format!("{} {} (= a b)", two, other)), Ok(Value::Boolean(false)));
+        assert_eq!(eval("(let c (list 1)) (set-car! c c) (= c c)"), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn circular_lists_print_with_datum_labels() {
+        let circular = eval("(let c (list 1 2)) (set-cdr! (cdr c) c) c").unwrap();
+        assert_eq!(circular.to_string(), "#0=(1 2 . #0#)");
+        let through_car = eval("(let c (list 1)) (set-car! c c) (list c \"s\")").unwrap();
+        assert_eq!(through_car.write_form(), "(#0=(#0#) \"s\")");
+        let shared = eval("(let s (list 1)) (list s s)").unwrap();
+        assert_eq!(shared.to_string(), "((1) (1))");
+    }
+
+    #[test]
+    fn circular_lists_are_not_proper_lists() {
+        let circular = eval("(let c (list 1 2)) (set-cdr! (cdr c) c) c").unwrap();
+        assert!(matches!(circular.into_vec(), Err(EvalError::TypeError(_))));
+        assert!(eval("(let c (list 1 2 3)) (set-cdr! (cdr (cdr c)) c) (apply + c)").is_err());
     }
 }
@@ -1,4 +1,10 @@
+#![allow(clippy::module_inception)]
+
 pub mod tokenizer;
 pub mod ast;
 pub mod parser;
-pub mod evaluator;
\ No newline at end of file
+pub mod evaluator;
+pub mod pretty_print;
+
+#[cfg(test)]
+mod test_util;
@@ -1,16 +1,30 @@
 use std::env;
 use std::fs;
 use std::io::{self, Write};
+use std::panic;
+use std::thread;
 
 use lisp::evaluator::Evaluator;
 use lisp::evaluator::Value;
-use lisp::parser::Parser;
+use lisp::parser::{Parser, ParserError};
 use lisp::tokenizer::Tokenizer;
 
+// Deep (non-tail) recursion in Lisp code nests evaluate calls, which needs far more
+// stack than the main thread gets by default
+const INTERPRETER_STACK_SIZE: usize = 256 * 1024 * 1024;
+
 fn main() -> io::Result<()> {
+    thread::Builder::new()
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(run)?
+        .join()
+        .unwrap_or_else(|payload| panic::resume_unwind(payload))
+}
+
+fn run() -> io::Result<()> {
     let args: Vec<String> = env::args().collect();
 
-    let evaluator = Evaluator::new();
+    let evaluator = Evaluator::with_stack_size(INTERPRETER_STACK_SIZE);
 
     match args.len() {
         1 => {
@@ -60,6 +74,7 @@ fn run_repl(evaluator: Evaluator) -> io::Result<()> {
                 }
 
                 match process_input(&evaluator, line) {
+                    Ok(Value::Void) => {}
                     Ok(value) => println!("{}", value),
                     Err(e) => eprintln!("Error: {}", e),
                 }
@@ -80,7 +95,7 @@ fn run_file(evaluator: Evaluator, file_path: &str) -> io::Result<()> {
 
     match process_input(&evaluator, &contents) {
         Ok(value) => {
-            if value != Value::Nil {
+            if value != Value::Nil && value != Value::Void {
                 println!("{}", value);
             }
         }
@@ -93,16 +108,12 @@ fn run_file(evaluator: Evaluator, file_path: &str) -> io::Result<()> {
 }
 
 fn process_input(evaluator: &Evaluator, input: &str) -> Result<Value, String> {
-    let mut tokenizer = Tokenizer::new(input);
-    let tokens = tokenizer
-        .tokenize()
-        .map_err(|e| format!("Tokenization Error: {}", e))?;
-
-    let mut parser = Parser::new(tokens);
+    let mut parser = Parser::new(Tokenizer::new(input));
 
-    let ast = parser
-        .parse()
-        .map_err(|e| format!("Parsing Error: {}", e))?;
+    let ast = parser.parse().map_err(|e| match e {
+        ParserError::Tokenizer(e) => format!("Tokenization Error: {}", e),
+        e => format!("Parsing Error: {}", e),
+    })?;
 
     let result = evaluator
         .eval_program(&ast)
@@ -1,3 +1,3 @@
 pub mod parser;
 
-pub use self::parser::Parser;
\ No newline at end of file
+pub use self::parser::{Parser, ParserError};
\ No newline at end of file
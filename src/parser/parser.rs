@@ -1,12 +1,14 @@
+use std::iter::Peekable;
 use std::{fmt, io};
 
-use crate::{ast::Expression, tokenizer::Token};
+use crate::{ast::Expression, tokenizer::{Token, TokenizerError}};
 
 #[derive(Debug, PartialEq)]
 pub enum ParserError {
     UnexpectedToken(Token, String),
     UnmatchedParenthesis,
     EndOfInput,
+    Tokenizer(TokenizerError),
 }
 
 impl fmt::Display for ParserError {
@@ -21,6 +23,9 @@ impl fmt::Display for ParserError {
             ParserError::EndOfInput => {
                 write!(f, "Unexpected end of input during parsing")
             }
+            ParserError::Tokenizer(error) => {
+                write!(f, "{}", error)
+            }
         }
     }
 }
@@ -32,60 +37,61 @@ impl From<ParserError> for io::Error {
 }
 
 #[derive(Debug)]
-pub struct Parser {
-    tokens: Vec<Token>,
-    current_token_index: usize,
+pub struct Parser<I: Iterator<Item = Result<Token, TokenizerError>>> {
+    tokens: Peekable<I>,
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
+impl<I: Iterator<Item = Result<Token, TokenizerError>>> Parser<I> {
+    pub fn new(tokens: I) -> Self {
         Parser {
-            tokens: tokens,
-            current_token_index: 0,
+            tokens: tokens.peekable(),
         }
     }
 
     pub fn parse(&mut self) -> Result<Vec<Expression>, ParserError> {
         let mut program = Vec::new();
 
-        while self.current_token_index < self.tokens.len() {
-            if self.check(&Token::Eof) {
-                break;
-            }
-
-            program.push(self.parse_expression()?);
+        while let Some(expr) = self.parse_one()? {
+            program.push(expr);
         }
         Ok(program)
     }
 
-    fn current_token(&self) -> Result<&Token, ParserError> {
-        self.tokens.get(self.current_token_index)
-            .ok_or(ParserError::EndOfInput)
+    pub fn parse_one(&mut self) -> Result<Option<Expression>, ParserError> {
+        if self.check(&Token::Eof) {
+            return Ok(None);
+        }
+        self.parse_expression().map(Some)
+    }
+
+    fn current_token(&mut self) -> Result<&Token, ParserError> {
+        match self.tokens.peek() {
+            Some(Ok(token)) => Ok(token),
+            Some(Err(error)) => Err(ParserError::Tokenizer(error.clone())),
+            None => Ok(&Token::Eof),
+        }
     }
 
     fn advance(&mut self) -> Result<(), ParserError> {
-        if self.current_token_index < self.tokens.len() {
-            self.current_token_index += 1;
-            Ok(())
-        } else {
-            Err(ParserError::EndOfInput)
+        match self.tokens.next() {
+            Some(Ok(_)) => Ok(()),
+            Some(Err(error)) => Err(ParserError::Tokenizer(error)),
+            None => Err(ParserError::EndOfInput),
         }
     }
 
     fn consume(&mut self, expected_token_type: Token) -> Result<(), ParserError> {
         let current = self.current_token()?.clone();
 
-        let match_found = match (&current, &expected_token_type) {
-            (Token::LeftParen, Token::LeftParen) => true,
-            (Token::RightParen, Token::RightParen) => true,
-            
-            (Token::Identifier(_), Token::Identifier(_)) => true,
-            (Token::String(_), Token::String(_)) => true,
-            (Token::Number(_), Token::Number(_)) => true,
-            (Token::Eof, Token::Eof) => true,
-
-            _ => false,
-        };
+        let match_found = matches!(
+            (&current, &expected_token_type),
+            (Token::LeftParen, Token::LeftParen)
+                | (Token::RightParen, Token::RightParen)
+                | (Token::Identifier(_), Token::Identifier(_))
+                | (Token::String(_), Token::String(_))
+                | (Token::Number(_), Token::Number(_))
+                | (Token::Eof, Token::Eof)
+        );
 
         if match_found {
             self.advance()?;
@@ -95,18 +101,18 @@ impl Parser {
         }
     }
 
-    fn check(&self, expected_token_type: &Token) -> bool {
+    fn check(&mut self, expected_token_type: &Token) -> bool {
         let current_result = self.current_token();
         if let Ok(current) = current_result {
-            match (current, expected_token_type) {
-                (Token::LeftParen, Token::LeftParen) => true,
-                (Token::RightParen, Token::RightParen) => true,
-                (Token::Identifier(_), Token::Identifier(_)) => true,
-                (Token::String(_), Token::String(_)) => true,
-                (Token::Number(_), Token::Number(_)) => true,
-                (Token::Eof, Token::Eof) => true,
-                _ => false,
-            }
+            matches!(
+                (current, expected_token_type),
+                (Token::LeftParen, Token::LeftParen)
+                    | (Token::RightParen, Token::RightParen)
+                    | (Token::Identifier(_), Token::Identifier(_))
+                    | (Token::String(_), Token::String(_))
+                    | (Token::Number(_), Token::Number(_))
+                    | (Token::Eof, Token::Eof)
+            )
         } else {
             false
         }
@@ -161,4 +167,29 @@ impl Parser {
         self.consume(Token::RightParen)?;
         Ok(Expression::List(elements))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn unclosed_list_is_an_error() {
+        assert_eq!(Parser::new(Tokenizer::new("(a")).parse(), Err(ParserError::UnmatchedParenthesis));
+    }
+
+    #[test]
+    fn parse_one_reads_a_single_expression() {
+        let mut parser = Parser::new(Tokenizer::new("(a 1) true"));
+        assert_eq!(
+            parser.parse_one(),
+            Ok(Some(Expression::List(vec![
+                Expression::Identifier("a".to_string()),
+                Expression::Number(1.0),
+            ])))
+        );
+        assert_eq!(parser.parse_one(), Ok(Some(Expression::Boolean(true))));
+        assert_eq!(parser.parse_one(), Ok(None));
+    }
+}
@@ -0,0 +1,3 @@
+pub mod pretty_print;
+
+pub use self::pretty_print::PrettyPrinter;
\ No newline at end of file
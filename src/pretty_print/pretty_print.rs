@@ -0,0 +1,66 @@
+use crate::evaluator::Value;
+
+// Forms whose body is indented under the head instead of aligned with the first argument
+const BODY_FORMS: &[&str] = &["lambda", "let", "do", "if"];
+
+#[derive(Debug)]
+pub struct PrettyPrinter {
+    width: usize,
+}
+
+impl Default for PrettyPrinter {
+    fn default() -> Self {
+        Self::new(80)
+    }
+}
+
+impl PrettyPrinter {
+    pub fn new(width: usize) -> Self {
+        PrettyPrinter { width }
+    }
+
+    pub fn format(&self, value: &Value) -> String {
+        self.format_at(value, 0)
+    }
+
+    fn format_at(&self, value: &Value, column: usize) -> String {
+        let flat = value.write_form();
+        // A circular value would be broken into lines forever, so it stays on one labelled line
+        if column + flat.chars().count() <= self.width || value.is_circular() {
+            return flat;
+        }
+
+        let Ok(items) = value.clone().into_vec() else {
+            return flat;
+        };
+        let Some((head, rest)) = items.split_first() else {
+            return flat;
+        };
+
+        let mut output = String::from("(");
+        output.push_str(&self.format_at(head, column + 1));
+
+        let is_body_form = matches!(head, Value::Symbol(name) if BODY_FORMS.contains(&name.as_str()));
+        if is_body_form && !rest.is_empty() {
+            // (lambda (params)
+            //   body ...)
+            let head_width = head.write_form().chars().count();
+            output.push(' ');
+            output.push_str(&self.format_at(&rest[0], column + head_width + 2));
+            for item in &rest[1..] {
+                output.push('\n');
+                output.push_str(&" ".repeat(column + 2));
+                output.push_str(&self.format_at(item, column + 2));
+            }
+        } else {
+            for item in rest {
+                output.push('\n');
+                output.push_str(&" ".repeat(column + 1));
+                output.push_str(&self.format_at(item, column + 1));
+            }
+        }
+
+        output.push(')');
+        output
+    }
+}
@@ -0,0 +1,106 @@
+(let not (lambda (x) (if x false true)))
+
+(let reverse
+  (lambda (lst)
+    (let loop ((lst lst) (acc (list)))
+      (if (null? lst)
+          acc
+          (loop (cdr lst) (cons (car lst) acc))))))
+
+(let length
+  (lambda (lst)
+    (let loop ((lst lst) (n 0))
+      (if (null? lst)
+          n
+          (loop (cdr lst) (+ n 1))))))
+
+(let fold-left
+  (lambda (f init lst)
+    (let loop ((acc init) (lst lst))
+      (if (null? lst)
+          acc
+          (loop (f acc (car lst)) (cdr lst))))))
+
+(let fold-right
+  (lambda (f init lst)
+    (fold-left (lambda (acc x) (f x acc)) init (reverse lst))))
+
+(let map
+  (lambda (f lst . more)
+    (if (null? more)
+        (reverse (fold-left (lambda (acc x) (cons (f x) acc)) (list) lst))
+        (let ((lists (cons lst more)))
+          (check-list-lengths "map" lists)
+          (let loop ((lists lists) (acc (list)))
+            (if (null? (car lists))
+                (reverse acc)
+                (loop (map cdr lists) (cons (apply f (map car lists)) acc))))))))
+
+(let filter
+  (lambda (pred lst)
+    (reverse (fold-left (lambda (acc x) (if (pred x) (cons x acc) acc)) (list) lst))))
+
+(let for-each
+  (lambda (f lst . more)
+    (if (null? more)
+        (do ((lst lst (cdr lst)))
+            ((null? lst))
+          (f (car lst)))
+        (let ((lists (cons lst more)))
+          (check-list-lengths "for-each" lists)
+          (do ((lists lists (map cdr lists)))
+              ((null? (car lists)))
+            (apply f (map car lists)))))))
+
+(let iota
+  (lambda (count . start-and-step)
+    (let ((start (if (null? start-and-step) 0 (car start-and-step)))
+          (step (if (and (pair? start-and-step) (pair? (cdr start-and-step)))
+                    (car (cdr start-and-step))
+                    1)))
+      (let loop ((i (- count 1)) (acc (list)))
+        (if (< i 0)
+            acc
+            (loop (- i 1) (cons (+ start (* i step)) acc)))))))
+
+(let any
+  (lambda (pred lst)
+    (let loop ((lst lst))
+      (if (null? lst)
+          false
+          (or (pred (car lst)) (loop (cdr lst)))))))
+
+(let every
+  (lambda (pred lst)
+    (let loop ((lst lst) (last-result true))
+      (if (null? lst)
+          last-result
+          (let ((result (pred (car lst))))
+            (if result (loop (cdr lst) result) false))))))
+
+(let assoc
+  (lambda (key alist)
+    (let loop ((alist alist))
+      (if (null? alist)
+          false
+          (if (= (car (car alist)) key)
+              (car alist)
+              (loop (cdr alist)))))))
+
+(let member
+  (lambda (x lst)
+    (let loop ((lst lst))
+      (if (null? lst)
+          false
+          (if (= (car lst) x)
+              lst
+              (loop (cdr lst)))))))
+
+(let list-index
+  (lambda (pred lst . more)
+    (let loop ((lists (cons lst more)) (index 0))
+      (if (any null? lists)
+          false
+          (if (apply pred (map car lists))
+              index
+              (loop (map cdr lists) (+ index 1)))))))
@@ -0,0 +1,21 @@
+// Helpers shared by the unit test modules
+use crate::ast::Expression;
+use crate::evaluator::{EvalError, Evaluator, Value};
+use crate::parser::Parser;
+use crate::tokenizer::Tokenizer;
+
+pub fn parse(source: &str) -> Vec<Expression> {
+    Parser::new(Tokenizer::new(source)).parse().expect("test source should parse")
+}
+
+pub fn eval(source: &str) -> Result<Value, EvalError> {
+    Evaluator::new().eval_program(&parse(source))
+}
+
+pub fn string(s: &str) -> Value {
+    Value::String(s.to_string())
+}
+
+pub fn numbers(items: &[f64]) -> Value {
+    Value::list(items.iter().map(|n| Value::Number(*n)).collect())
+}
@@ -2,7 +2,7 @@ use std::{fmt, io};
 
 use crate::tokenizer::token::Token; 
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TokenizerError {
     UnexpectedCharacter(char, usize),
     UnterminatedString(usize),
@@ -51,6 +51,47 @@ impl Tokenizer {
         }
     }
 
+    pub fn new_incremental() -> Self {
+        Tokenizer::new("")
+    }
+
+    pub fn feed(&mut self, chunk: &str) {
+        self.source.extend(chunk.chars());
+        self.current_char = self.source.get(self.current_position).copied();
+    }
+
+    pub fn next_token_or_incomplete(&mut self) -> Result<Option<Token>, TokenizerError> {
+        let start_pos = self.current_position;
+
+        self.skip_whitespace();
+        if self.current_char.is_none() {
+            return Ok(None);
+        }
+
+        match self.next_token() {
+            Ok(token) => {
+                // An atom that runs into the end of the buffer may continue in the next chunk
+                let at_end = self.current_char.is_none()
+                    || (self.current_char == Some('.') && self.peek().is_none());
+                if at_end && matches!(token, Token::Number(_) | Token::Identifier(_)) {
+                    self.rewind(start_pos);
+                    return Ok(None);
+                }
+                Ok(Some(token))
+            }
+            Err(TokenizerError::UnterminatedString(_)) => {
+                self.rewind(start_pos);
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn rewind(&mut self, position: usize) {
+        self.current_position = position;
+        self.current_char = self.source.get(position).copied();
+    }
+
     fn advance(&mut self) -> Option<char> {
         let consumed_char = self.current_char;
         self.current_position += 1;
@@ -107,22 +148,33 @@ impl Tokenizer {
         let start_char_pos = self.current_position;
         self.advance(); // Consume the opening '"'
 
-        let string_content_start = self.current_position;
-
+        let mut string_value = String::new();
         while let Some(c) = self.current_char {
             if c == '"' {
                 break;
             }
             self.advance();
+            if c != '\\' {
+                string_value.push(c);
+                continue;
+            }
+            // The escapes that write produces; any other backslash is kept as written
+            match self.current_char {
+                Some('n') => string_value.push('\n'),
+                Some('t') => string_value.push('\t'),
+                Some(escaped @ ('"' | '\\')) => string_value.push(escaped),
+                Some(other) => {
+                    string_value.push('\\');
+                    string_value.push(other);
+                }
+                None => break,
+            }
+            self.advance();
         }
 
         if self.current_char != Some('"') {
             return Err(TokenizerError::UnterminatedString(start_char_pos));
         }
-
-        let string_value: String = self.source[string_content_start..self.current_position]
-            .iter()
-            .collect();
         self.advance();
         Ok(Token::String(string_value))
     }
@@ -131,17 +183,17 @@ impl Tokenizer {
         let start_pos = self.current_position;
 
         while let Some(c) = self.current_char {
-            if c.is_digit(10) {
+            if c.is_ascii_digit() {
                 self.advance();
             } else {
                 break;
             }
         }
 
-        if self.current_char == Some('.') && self.peek().map_or(false, |c| c.is_digit(10)) {
+        if self.current_char == Some('.') && self.peek().is_some_and(|c| c.is_ascii_digit()) {
             self.advance();
             while let Some(c) = self.current_char {
-                if c.is_digit(10) {
+                if c.is_ascii_digit() {
                     self.advance();
                 } else {
                     break;
@@ -174,11 +226,21 @@ impl Tokenizer {
             .iter()
             .collect();
 
+        // Infinities and NaN have no digits to start read_number, so they are recognised by name
+        let special_number = match identifier_str.as_str() {
+            "+inf.0" => Some(f64::INFINITY),
+            "-inf.0" => Some(f64::NEG_INFINITY),
+            "+nan.0" | "-nan.0" => Some(f64::NAN),
+            _ => None,
+        };
+
         if identifier_str.is_empty() {
             Err(TokenizerError::UnexpectedCharacter(
                 self.current_char.unwrap_or('\0'),
                 start_pos,
             ))
+        } else if let Some(value) = special_number {
+            Ok(Token::Number(value))
         } else {
             Ok(Token::Identifier(identifier_str))
         }
@@ -203,3 +265,73 @@ impl Tokenizer {
         Ok(tokens)
     }
 }
+
+impl Iterator for Tokenizer {
+    type Item = Result<Token, TokenizerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_token() {
+            Ok(Token::Eof) => None,
+            result => Some(result),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn incremental_tokenizer_waits_for_complete_atoms() {
+        let mut tokenizer = Tokenizer::new_incremental();
+        tokenizer.feed("(fo");
+        assert_eq!(tokenizer.next_token_or_incomplete(), Ok(Some(Token::LeftParen)));
+        assert_eq!(tokenizer.next_token_or_incomplete(), Ok(None));
+        tokenizer.feed("o \"ab");
+        assert_eq!(tokenizer.next_token_or_incomplete(), Ok(Some(Token::Identifier("foo".to_string()))));
+        assert_eq!(tokenizer.next_token_or_incomplete(), Ok(None));
+        tokenizer.feed("c\")");
+        assert_eq!(tokenizer.next_token_or_incomplete(), Ok(Some(Token::String("abc".to_string()))));
+        assert_eq!(tokenizer.next_token_or_incomplete(), Ok(Some(Token::RightParen)));
+        assert_eq!(tokenizer.next_token_or_incomplete(), Ok(None));
+    }
+
+    #[test]
+    fn iterates_over_tokens_until_eof() {
+        let tokens: Result<Vec<Token>, _> = Tokenizer::new("(+ 1 \"a\")").collect();
+        assert_eq!(
+            tokens,
+            Ok(vec![
+                Token::LeftParen,
+                Token::Identifier("+".to_string()),
+                Token::Number(1.0),
+                Token::String("a".to_string()),
+                Token::RightParen,
+            ])
+        );
+    }
+
+    #[test]
+    fn reads_string_escapes() {
+        let tokens: Vec<Token> = Tokenizer::new(r#""a\"b" "x\\y" "1\n2\t" "c:\dir""#).map(Result::unwrap).collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::String("a\"b".to_string()),
+                Token::String("x\\y".to_string()),
+                Token::String("1\n2\t".to_string()),
+                Token::String("c:\\dir".to_string()),
+            ]
+        );
+        assert_eq!(Tokenizer::new(r#""ab\"#).next(), Some(Err(TokenizerError::UnterminatedString(0))));
+    }
+
+    #[test]
+    fn reads_infinities_and_nan_as_numbers() {
+        let tokens: Vec<Token> = Tokenizer::new("+inf.0 -inf.0 +nan.0 +").map(Result::unwrap).collect();
+        assert_eq!(tokens[0], Token::Number(f64::INFINITY));
+        assert_eq!(tokens[1], Token::Number(f64::NEG_INFINITY));
+        assert!(matches!(tokens[2], Token::Number(n) if n.is_nan()));
+        assert_eq!(tokens[3], Token::Identifier("+".to_string()));
+    }
+}